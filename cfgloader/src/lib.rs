@@ -55,7 +55,7 @@
 //!     #[env("APP_NAME", required)]
 //!     name: String,
 //!     
-//!     // Nested structs automatically call their own load() method
+//!     // Nested structs are loaded through their own FromEnv implementation
 //!     server: ServerConfig,
 //!     database: DatabaseConfig,
 //! }
@@ -88,10 +88,64 @@
 //!     Ok(())
 //! }
 //! ```
+//!
+//! ### Key Prefixes
+//!
+//! Prefixes on nested fields and structs are prepended to every key below them,
+//! so one struct can be loaded under several namespaces:
+//!
+//! ```rust,no_run
+//! use cfgloader_rs::*;
+//!
+//! #[derive(FromEnv, Debug)]
+//! #[env(prefix = "APP_")]
+//! struct AppConfig {
+//!     // APP_PRIMARY_DB_URL
+//!     #[env(prefix = "PRIMARY_")]
+//!     primary: DatabaseConfig,
+//!
+//!     // APP_REPLICA_DB_URL
+//!     #[env(prefix = "REPLICA_")]
+//!     replica: DatabaseConfig,
+//! }
+//!
+//! #[derive(FromEnv, Debug)]
+//! #[env(prefix = "DB_")]
+//! struct DatabaseConfig {
+//!     #[env("URL", required)]
+//!     url: String,
+//! }
+//!
+//! fn main() -> Result<(), Box<dyn std::error::Error>> {
+//!     let config = AppConfig::load(std::path::Path::new(".env"))?;
+//!     println!("Replica: {}", config.replica.url);
+//!     Ok(())
+//! }
+//! ```
+//!
+//! ### Enums
+//!
+//! Unit-only enums deriving [`EnvValue`] are parsed from their variant names,
+//! case-insensitively:
+//!
+//! ```rust
+//! use cfgloader_rs::*;
+//!
+//! #[derive(EnvValue, Debug, PartialEq)]
+//! enum Tier {
+//!     Development,
+//!     #[env(rename = "prod", alias = "production")]
+//!     Production,
+//! }
+//!
+//! assert_eq!("PROD".parse::<Tier>().unwrap(), Tier::Production);
+//! assert_eq!("development".parse::<Tier>().unwrap(), Tier::Development);
+//! assert!("staging".parse::<Tier>().is_err());
+//! ```
 
 // Re-export all core functionality
 pub use cfgloader_core::*;
 
 // Re-export derive macro when derive feature is enabled
 #[cfg(feature = "derive")]
-pub use cfgloader_rs_macros::FromEnv;
+pub use cfgloader_rs_macros::{EnvValue, FromEnv};
@@ -0,0 +1,429 @@
+//! Loading derived configs from the environment. Every test loads under its
+//! own prefix, so tests running in parallel never see each other's variables.
+
+use cfgloader_rs::*;
+use std::collections::BTreeMap;
+
+fn set_vars(vars: &[(&str, &str)]) {
+    for (key, value) in vars {
+        // SAFETY: the tests only access the environment through std, which
+        // serializes every access with its own lock
+        unsafe { std::env::set_var(key, value) };
+    }
+}
+
+/// The path and message of every failure in a `CfgError::Multiple`
+fn failures(err: CfgError) -> Vec<(String, String)> {
+    match err {
+        CfgError::Multiple(errors) => errors
+            .into_iter()
+            .map(|FieldError { path, error }| (path, error.to_string()))
+            .collect(),
+        err => panic!("expected CfgError::Multiple, got {err:?}"),
+    }
+}
+
+#[derive(FromEnv, Debug)]
+struct Server {
+    #[env("PORT", required)]
+    port: u16,
+
+    #[env(indexed = "UPSTREAM_")]
+    upstreams: Vec<Upstream>,
+
+    #[env(keyed = "DB_")]
+    databases: BTreeMap<String, Database>,
+}
+
+#[derive(FromEnv, Debug)]
+struct Upstream {
+    #[env("HOST", required)]
+    host: String,
+
+    #[env("PORT", default = "80")]
+    port: u16,
+}
+
+#[derive(FromEnv, Debug, PartialEq)]
+struct Database {
+    #[env("URL", required)]
+    url: String,
+
+    #[env("POOL_SIZE", default = "10")]
+    pool_size: u32,
+}
+
+#[test]
+fn load_all_reports_every_failure_with_its_path() {
+    set_vars(&[
+        ("ALL_A_PORT", "http"),
+        ("ALL_A_UPSTREAM_0_HOST", "a.internal"),
+        ("ALL_A_UPSTREAM_1_PORT", "8080"),
+        ("ALL_A_DB_PRIMARY_POOL_SIZE", "5"),
+        ("ALL_A_DB_REPLICA_URL", "postgres://replica"),
+    ]);
+    let err = Server::load_prefixed_all("ALL_A_").unwrap_err();
+    assert_eq!(
+        failures(err),
+        [
+            (
+                "port".into(),
+                "failed to parse env ALL_A_PORT value `http` into u16".into()
+            ),
+            (
+                "upstreams[1].host".into(),
+                "missing required env: ALL_A_UPSTREAM_1_HOST".into()
+            ),
+            (
+                "databases[PRIMARY].url".into(),
+                "missing required env: ALL_A_DB_PRIMARY_URL".into()
+            ),
+        ]
+    );
+
+    // load_prefixed stops at the first failure
+    let err = Server::load_prefixed("ALL_A_").unwrap_err();
+    assert!(matches!(err, CfgError::ParseError { .. }), "{err:?}");
+}
+
+#[test]
+fn load_all_succeeds_like_load() {
+    set_vars(&[
+        ("ALL_B_PORT", "8080"),
+        ("ALL_B_UPSTREAM_0_HOST", "a.internal"),
+        ("ALL_B_UPSTREAM_1_HOST", "b.internal"),
+        ("ALL_B_UPSTREAM_1_PORT", "8080"),
+        ("ALL_B_DB_READ_REPLICA_URL", "postgres://replica"),
+    ]);
+    let server = Server::load_prefixed_all("ALL_B_").unwrap();
+    assert_eq!(server.port, 8080);
+    let upstreams: Vec<_> = server
+        .upstreams
+        .iter()
+        .map(|u| (u.host.as_str(), u.port))
+        .collect();
+    assert_eq!(upstreams, [("a.internal", 80), ("b.internal", 8080)]);
+    assert_eq!(
+        server.databases,
+        [(
+            "READ_REPLICA".to_string(),
+            Database {
+                url: "postgres://replica".into(),
+                pool_size: 10
+            }
+        )]
+        .into()
+    );
+}
+
+#[test]
+fn indexed_scan_stops_at_the_first_gap() {
+    set_vars(&[
+        ("INDEXED_A_UPSTREAM_0_HOST", "a"),
+        ("INDEXED_A_UPSTREAM_1_HOST", "b"),
+        // Index 2 is missing, so index 3 is never read
+        ("INDEXED_A_UPSTREAM_3_HOST", "d"),
+        // Blank values do not count as set
+        ("INDEXED_B_UPSTREAM_0_HOST", " "),
+        ("INDEXED_B_UPSTREAM_1_HOST", "b"),
+    ]);
+    let hosts = |prefix| -> Vec<String> {
+        #[derive(FromEnv)]
+        struct List {
+            #[env(indexed = "UPSTREAM_")]
+            upstreams: Vec<Upstream>,
+        }
+        let list = List::load_prefixed(prefix).unwrap();
+        list.upstreams.into_iter().map(|u| u.host).collect()
+    };
+    assert_eq!(hosts("INDEXED_A_"), ["a", "b"]);
+    assert!(hosts("INDEXED_B_").is_empty());
+}
+
+#[test]
+fn option_is_none_when_unset_or_blank() {
+    #[derive(FromEnv)]
+    struct Config {
+        #[env("TIMEOUT")]
+        timeout: Option<u32>,
+        #[env("NAME")]
+        name: Option<String>,
+        #[env("LEVEL")]
+        level: Option<String>,
+    }
+    set_vars(&[("OPTION_A_NAME", "  "), ("OPTION_A_LEVEL", "debug")]);
+    let cfg = Config::load_prefixed("OPTION_A_").unwrap();
+    assert_eq!(cfg.timeout, None);
+    assert_eq!(cfg.name, None);
+    assert_eq!(cfg.level.as_deref(), Some("debug"));
+}
+
+#[test]
+fn nested_prefixes_compose_into_full_keys() {
+    #[derive(FromEnv)]
+    #[env(prefix = "APP_")]
+    struct App {
+        #[env(prefix = "PRIMARY_")]
+        primary: Db,
+        #[env(prefix = "REPLICA_")]
+        replica: Db,
+    }
+
+    #[derive(FromEnv)]
+    #[env(prefix = "DB_")]
+    struct Db {
+        #[env("URL", required)]
+        url: String,
+    }
+
+    set_vars(&[("NESTED_A_APP_PRIMARY_DB_URL", "postgres://primary")]);
+    let err = App::load_prefixed("NESTED_A_").err().unwrap();
+    assert_eq!(
+        err.to_string(),
+        "missing required env: NESTED_A_APP_REPLICA_DB_URL"
+    );
+
+    set_vars(&[("NESTED_A_APP_REPLICA_DB_URL", "postgres://replica")]);
+    let app = App::load_prefixed("NESTED_A_").unwrap();
+    assert_eq!(app.primary.url, "postgres://primary");
+    assert_eq!(app.replica.url, "postgres://replica");
+}
+
+#[derive(FromEnv, Debug)]
+struct Aliased {
+    #[env(
+        "DATABASE_URL",
+        alias = "DB_URL",
+        deprecated = "DB_URI",
+        deprecated = "DB_CONNECTION",
+        required
+    )]
+    url: String,
+}
+
+#[test]
+fn aliases_are_tried_in_order_and_deprecations_reported() {
+    set_vars(&[
+        ("ALIAS_A_DB_URI", "uri"),
+        ("ALIAS_A_DB_CONNECTION", "connection"),
+    ]);
+    let mut report = LoadReport::default();
+    let cfg = Aliased::load_prefixed_with_report("ALIAS_A_", &mut report).unwrap();
+    assert_eq!(cfg.url, "uri");
+    assert_eq!(
+        report.deprecations,
+        [Deprecation {
+            key: "ALIAS_A_DB_URI".into(),
+            replacement: "ALIAS_A_DATABASE_URL".into(),
+        }]
+    );
+
+    // An alias wins over deprecated keys and is not reported
+    set_vars(&[("ALIAS_A_DB_URL", "alias")]);
+    let mut report = LoadReport::default();
+    let cfg = Aliased::load_prefixed_all_with_report("ALIAS_A_", &mut report).unwrap();
+    assert_eq!(cfg.url, "alias");
+    assert!(report.deprecations.is_empty());
+
+    set_vars(&[("ALIAS_A_DATABASE_URL", "current")]);
+    assert_eq!(Aliased::load_prefixed("ALIAS_A_").unwrap().url, "current");
+}
+
+#[test]
+fn missing_env_lists_the_key_then_its_alternatives() {
+    match Aliased::load_prefixed("ALIAS_B_").unwrap_err() {
+        CfgError::MissingEnv(keys) => assert_eq!(
+            keys,
+            [
+                "ALIAS_B_DATABASE_URL",
+                "ALIAS_B_DB_URL",
+                "ALIAS_B_DB_URI",
+                "ALIAS_B_DB_CONNECTION",
+            ]
+        ),
+        err => panic!("expected CfgError::MissingEnv, got {err:?}"),
+    }
+}
+
+#[test]
+fn load_all_with_report_keeps_deprecations() {
+    let env_file =
+        std::env::temp_dir().join(format!("cfgloader-report-{}.env", std::process::id()));
+    std::fs::write(&env_file, "ALIAS_C_DB_CONNECTION=connection\n").unwrap();
+    #[derive(FromEnv)]
+    #[env(prefix = "ALIAS_C_")]
+    struct Config {
+        #[env(prefix = "")]
+        db: Aliased,
+    }
+    let result = Config::load_all_with_report(&env_file);
+    std::fs::remove_file(&env_file).unwrap();
+    let (cfg, report) = result.unwrap();
+    assert_eq!(cfg.db.url, "connection");
+    assert_eq!(
+        report.deprecations,
+        [Deprecation {
+            key: "ALIAS_C_DB_CONNECTION".into(),
+            replacement: "ALIAS_C_DATABASE_URL".into(),
+        }]
+    );
+}
+
+#[test]
+fn newtypes_carry_their_key() {
+    #[derive(FromEnv, Debug, PartialEq)]
+    struct Port(#[env("PORT", default = "8080")] u16);
+
+    #[derive(FromEnv, Debug, PartialEq)]
+    #[env("API_KEY", required)]
+    struct ApiKey(String);
+
+    #[derive(FromEnv)]
+    struct Config {
+        port: Port,
+        api_key: ApiKey,
+    }
+
+    let err = Config::load_prefixed("NEWTYPE_A_").err().unwrap();
+    assert_eq!(err.to_string(), "missing required env: NEWTYPE_A_API_KEY");
+
+    set_vars(&[
+        ("NEWTYPE_A_API_KEY", "secret"),
+        ("NEWTYPE_A_PORT", "0x1F90"),
+    ]);
+    let cfg = Config::load_prefixed("NEWTYPE_A_").unwrap();
+    assert_eq!(cfg.port, Port(8080));
+    assert_eq!(cfg.api_key, ApiKey("secret".into()));
+}
+
+#[test]
+fn generic_fields_get_the_bounds_they_need() {
+    #[derive(FromEnv)]
+    struct WithRetry<T> {
+        inner: T,
+        #[env("RETRIES", default = "3")]
+        retries: u32,
+    }
+
+    #[derive(FromEnv)]
+    struct Setting<V> {
+        #[env("VALUE", required)]
+        value: V,
+        #[env("ITEMS")]
+        items: Vec<V>,
+        #[env("FALLBACK", default)]
+        fallback: V,
+    }
+
+    set_vars(&[
+        ("GENERIC_A_URL", "postgres://db"),
+        ("GENERIC_A_VALUE", "7"),
+        ("GENERIC_A_ITEMS", "1, 2"),
+    ]);
+    let db = WithRetry::<Database>::load_prefixed("GENERIC_A_").unwrap();
+    assert_eq!(db.inner.url, "postgres://db");
+    assert_eq!(db.retries, 3);
+
+    let setting = Setting::<u8>::load_prefixed("GENERIC_A_").unwrap();
+    assert_eq!(
+        (setting.value, setting.items, setting.fallback),
+        (7, vec![1, 2], 0)
+    );
+    let setting = Setting::<String>::load_prefixed("GENERIC_A_").unwrap();
+    assert_eq!(setting.fallback, "");
+}
+
+#[derive(FromEnv, Debug)]
+struct Sections {
+    #[env(prefix = "REDIS_", enabled_by = "REDIS_ENABLED")]
+    redis: Option<Redis>,
+
+    #[env(prefix = "SENTRY_")]
+    sentry: Option<Sentry>,
+}
+
+#[derive(FromEnv, Debug)]
+struct Redis {
+    #[env("URL", required)]
+    url: String,
+}
+
+#[derive(FromEnv, Debug)]
+struct Sentry {
+    #[env("DSN", required)]
+    dsn: String,
+
+    #[env("RATE", default = "1.0")]
+    rate: f64,
+}
+
+#[test]
+fn sections_are_skipped_until_enabled_or_present() {
+    // A toggle-less section is not enabled by a blank key
+    set_vars(&[
+        ("SECTION_A_REDIS_URL", "redis://a"),
+        ("SECTION_A_SENTRY_DSN", " "),
+    ]);
+    let cfg = Sections::load_prefixed("SECTION_A_").unwrap();
+    assert!(
+        cfg.redis.is_none(),
+        "keys alone do not enable a toggled section"
+    );
+    assert!(cfg.sentry.is_none());
+
+    set_vars(&[
+        ("SECTION_B_REDIS_ENABLED", "yes"),
+        ("SECTION_B_SENTRY_RATE", "0.5"),
+    ]);
+    let err = Sections::load_prefixed_all("SECTION_B_").unwrap_err();
+    assert_eq!(
+        failures(err),
+        [
+            (
+                "redis.url".into(),
+                "missing required env: SECTION_B_REDIS_URL".into()
+            ),
+            (
+                "sentry.dsn".into(),
+                "missing required env: SECTION_B_SENTRY_DSN".into()
+            ),
+        ]
+    );
+
+    set_vars(&[
+        ("SECTION_C_REDIS_ENABLED", "on"),
+        ("SECTION_C_REDIS_URL", "redis://c"),
+        ("SECTION_C_SENTRY_DSN", "https://sentry"),
+        ("SECTION_D_REDIS_ENABLED", "off"),
+    ]);
+    assert!(
+        Sections::load_prefixed("SECTION_D_")
+            .unwrap()
+            .redis
+            .is_none()
+    );
+    let cfg = Sections::load_prefixed("SECTION_C_").unwrap();
+    assert_eq!(cfg.redis.map(|r| r.url).as_deref(), Some("redis://c"));
+    assert_eq!(
+        cfg.sentry.map(|s| (s.dsn, s.rate)),
+        Some(("https://sentry".into(), 1.0))
+    );
+}
+
+#[test]
+fn load_reads_the_env_file_first() {
+    let env_file = std::env::temp_dir().join(format!("cfgloader-load-{}.env", std::process::id()));
+    std::fs::write(&env_file, "LOAD_A_PORT=9090\nLOAD_A_UPSTREAM_0_HOST=a\n").unwrap();
+    #[derive(FromEnv)]
+    #[env(prefix = "LOAD_A_")]
+    struct Config {
+        #[env(prefix = "")]
+        server: Server,
+    }
+    let result = Config::load(&env_file);
+    std::fs::remove_file(&env_file).unwrap();
+    let cfg = result.unwrap();
+    assert_eq!(cfg.server.port, 9090);
+    assert_eq!(cfg.server.upstreams.len(), 1);
+    assert!(cfg.server.databases.is_empty());
+}
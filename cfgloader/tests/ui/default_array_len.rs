@@ -0,0 +1,9 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("RGB", default = "1,2")]
+    rgb: [u8; 3],
+}
+
+fn main() {}
@@ -0,0 +1,13 @@
+use cfgloader_rs::*;
+use std::collections::HashSet;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("PORTS", default = "80, 443, 0x50")]
+    ports: HashSet<u16>,
+    // Duplicates are dropped with `dedup`
+    #[env("CODES", default = "1,1", dedup)]
+    codes: HashSet<u8>,
+}
+
+fn main() {}
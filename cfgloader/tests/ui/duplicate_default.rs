@@ -0,0 +1,9 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("PORT", default = "80", default = "8080")]
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,11 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("HX", default_expr = keys[0].clone())]
+    hx: String,
+    #[env("PORT", default = 80, range(1..=value))]
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env(default = "80", "PORT")]
+    port: u16,
+}
+
+fn main() {}
@@ -0,0 +1,9 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("HOSTS", split = SEPARATOR)]
+    hosts: Vec<String>,
+}
+
+fn main() {}
@@ -0,0 +1,16 @@
+use cfgloader_rs::*;
+use std::collections::HashMap;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env(keyed = "DB_", prefix = "MAIN_")]
+    databases: HashMap<String, Database>,
+}
+
+#[derive(FromEnv)]
+struct Database {
+    #[env("URL", required)]
+    url: String,
+}
+
+fn main() {}
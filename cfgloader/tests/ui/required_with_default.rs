@@ -0,0 +1,9 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("PORT", required, default = "80")]
+    port: u16,
+}
+
+fn main() {}
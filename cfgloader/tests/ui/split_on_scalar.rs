@@ -0,0 +1,9 @@
+use cfgloader_rs::*;
+
+#[derive(FromEnv)]
+struct Config {
+    #[env("PORT", split = ",")]
+    port: u16,
+}
+
+fn main() {}
@@ -17,10 +17,12 @@ pub mod fallback {
 }
 
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum CfgError {
-    MissingEnv(&'static str),
+    /// None of the keys was set; the first is the field's key, the rest its aliases
+    MissingEnv(Vec<String>),
     ParseError {
-        key: &'static str,
+        key: String,
         value: String,
         ty: &'static str,
         source: Box<dyn std::error::Error + Send + Sync>,
@@ -29,12 +31,48 @@ pub enum CfgError {
         msg: &'static str,
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+    /// Loading element `index` of an `#[env(indexed = "...")]` field failed
+    IndexedError {
+        prefix: String,
+        index: usize,
+        source: Box<CfgError>,
+    },
+    /// The value of `key` parsed, but failed a validation rule such as `range(1..=10)`
+    ValidationError {
+        key: String,
+        value: String,
+        rule: String,
+    },
+    /// A constraint between keys such as `requires = "TLS_KEY"` does not hold
+    ConstraintError { key: String, rule: String },
+    /// The struct-level `#[env(validate = ...)]` function rejected the loaded `ty`
+    StructValidationError {
+        ty: &'static str,
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    /// Loading the `name` group of an `#[env(keyed = "...")]` field failed
+    KeyedError {
+        prefix: String,
+        name: String,
+        source: Box<CfgError>,
+    },
+    /// A `${VAR}` reference in the value of `key` could not be expanded, see [`interpolate`]
+    InterpolationError { key: String, msg: String },
+    /// Every failure found by [`FromEnv::load_all`], each with its field path
+    Multiple(Vec<FieldError>),
 }
 
 impl std::fmt::Display for CfgError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            CfgError::MissingEnv(key) => write!(f, "missing required env: {}", key),
+            CfgError::MissingEnv(keys) => match keys.as_slice() {
+                [key] => write!(f, "missing required env: {}", key),
+                keys => write!(
+                    f,
+                    "missing required env: none of {} is set",
+                    keys.join(", ")
+                ),
+            },
             CfgError::ParseError { key, value, ty, .. } => {
                 write!(
                     f,
@@ -43,6 +81,28 @@ impl std::fmt::Display for CfgError {
                 )
             }
             CfgError::LoadError { msg, .. } => write!(f, "failed to load env: {}", msg),
+            CfgError::ValidationError { key, value, rule } => {
+                write!(f, "env {} value `{}` does not satisfy {}", key, value, rule)
+            }
+            CfgError::ConstraintError { key, rule } => write!(f, "env {} {}", key, rule),
+            CfgError::StructValidationError { ty, .. } => write!(f, "invalid {}", ty),
+            CfgError::IndexedError { prefix, index, .. } => {
+                write!(f, "failed to load element {} of {}", index, prefix)
+            }
+            CfgError::KeyedError { prefix, name, .. } => {
+                write!(f, "failed to load entry {} of {}", name, prefix)
+            }
+            CfgError::InterpolationError { key, msg } => {
+                write!(f, "failed to interpolate env {}: {}", key, msg)
+            }
+            CfgError::Multiple(errors) => {
+                let plural = if errors.len() == 1 { "" } else { "s" };
+                write!(f, "{} configuration error{}", errors.len(), plural)?;
+                for error in errors {
+                    write!(f, "\n  {}", error)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -50,15 +110,154 @@ impl std::fmt::Display for CfgError {
 impl std::error::Error for CfgError {
     fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
         match self {
-            CfgError::MissingEnv(_) => None,
+            CfgError::MissingEnv(_)
+            | CfgError::ValidationError { .. }
+            | CfgError::ConstraintError { .. }
+            | CfgError::InterpolationError { .. }
+            | CfgError::Multiple(_) => None,
+            CfgError::StructValidationError { source, .. } => Some(source.as_ref()),
             CfgError::ParseError { source, .. } => Some(source.as_ref()),
             CfgError::LoadError { source, .. } => Some(source.as_ref()),
+            CfgError::IndexedError { source, .. } => Some(source.as_ref()),
+            CfgError::KeyedError { source, .. } => Some(source.as_ref()),
         }
     }
 }
 
+/// A failure while loading one field, as collected by [`FromEnv::load_all`]
+#[derive(Debug)]
+pub struct FieldError {
+    /// Path of the field from the loaded struct, e.g. `database.url` or
+    /// `upstreams[1].host`; empty for a failure of the struct itself
+    pub path: String,
+    pub error: CfgError,
+}
+
+impl FieldError {
+    pub fn new(path: impl Into<String>, error: CfgError) -> Self {
+        FieldError {
+            path: path.into(),
+            error,
+        }
+    }
+}
+
+impl std::fmt::Display for FieldError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if !self.path.is_empty() {
+            write!(f, "{}: ", self.path)?;
+        }
+        write!(f, "{}", self.error)?;
+        // Shown inline, as the errors of a list can't be reached through `source`
+        if let Some(source) = std::error::Error::source(&self.error) {
+            write!(f, ": {}", source)?;
+        }
+        Ok(())
+    }
+}
+
+impl From<CfgError> for Vec<FieldError> {
+    fn from(error: CfgError) -> Self {
+        vec![FieldError::new(String::new(), error)]
+    }
+}
+
+/// Error returned by the `FromStr` implementation generated by `#[derive(EnvValue)]`
+#[derive(Debug)]
+pub struct ParseEnumError {
+    pub value: String,
+    /// Every accepted spelling, including aliases
+    pub expected: &'static [&'static str],
+}
+
+impl std::fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "unknown value `{}`, expected one of: {}",
+            self.value,
+            self.expected.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ParseEnumError {}
+
+/// A deprecated key that was used to load a value
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Deprecation {
+    /// The deprecated key that was set
+    pub key: String,
+    /// The key that should be used instead
+    pub replacement: String,
+}
+
+impl std::fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} is deprecated, use {}", self.key, self.replacement)
+    }
+}
+
+/// Notices collected while loading a configuration
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LoadReport {
+    pub deprecations: Vec<Deprecation>,
+}
+
 pub trait FromEnv: Sized {
     fn load(env_path: &std::path::Path) -> Result<Self, CfgError>;
+    /// Like [`FromEnv::load`], also returning the notices collected while loading
+    fn load_with_report(env_path: &std::path::Path) -> Result<(Self, LoadReport), CfgError> {
+        load_env_file(env_path)?;
+        let mut report = LoadReport::default();
+        let cfg = Self::load_prefixed_with_report("", &mut report)?;
+        Ok((cfg, report))
+    }
+    /// Like [`FromEnv::load`], but tries every field and returns all failures
+    /// at once as [`CfgError::Multiple`]
+    fn load_all(env_path: &std::path::Path) -> Result<Self, CfgError> {
+        load_env_file(env_path)?;
+        Self::load_prefixed_all("")
+    }
+    /// Like [`FromEnv::load_all`], also returning the notices collected while loading
+    fn load_all_with_report(env_path: &std::path::Path) -> Result<(Self, LoadReport), CfgError> {
+        load_env_file(env_path)?;
+        let mut report = LoadReport::default();
+        let cfg = Self::load_prefixed_all_with_report("", &mut report)?;
+        Ok((cfg, report))
+    }
+    /// Like [`FromEnv::load_prefixed`], returning all failures at once
+    fn load_prefixed_all(prefix: &str) -> Result<Self, CfgError> {
+        Self::load_prefixed_all_with_report(prefix, &mut LoadReport::default())
+    }
+    /// Like [`FromEnv::load_prefixed_all`], recording notices in `report`
+    fn load_prefixed_all_with_report(
+        prefix: &str,
+        report: &mut LoadReport,
+    ) -> Result<Self, CfgError> {
+        Self::load_fields(prefix, report).map_err(CfgError::Multiple)
+    }
+    /// Load under `prefix`, collecting the failure of every field with its path
+    #[doc(hidden)]
+    fn load_fields(prefix: &str, report: &mut LoadReport) -> Result<Self, Vec<FieldError>> {
+        Ok(Self::load_prefixed_with_report(prefix, report)?)
+    }
+    /// Load from the current environment, prepending `prefix` to every key.
+    ///
+    /// Unlike [`FromEnv::load`] this does not read a .env file.
+    fn load_prefixed(prefix: &str) -> Result<Self, CfgError> {
+        Self::load_prefixed_with_report(prefix, &mut LoadReport::default())
+    }
+    /// Like [`FromEnv::load_prefixed`], recording notices in `report`
+    fn load_prefixed_with_report(prefix: &str, report: &mut LoadReport) -> Result<Self, CfgError>;
+    /// Keys read by [`FromEnv::load_prefixed`], relative to its prefix.
+    ///
+    /// Used to discover the groups of `#[env(keyed = "...")]` fields. Keys
+    /// under indexed and keyed fields are not listed.
+    #[doc(hidden)]
+    fn env_keys() -> Vec<String> {
+        Vec::new()
+    }
     fn load_iter<I, P>(paths: I) -> Result<Self, CfgError>
     where
         I: IntoIterator<Item = P>,
@@ -66,21 +265,430 @@ pub trait FromEnv: Sized {
 }
 
 /// Utility function for macros: read env and return `Option<String>`
-pub fn get_env(key: &'static str) -> Option<String> {
+pub fn get_env(key: &str) -> Option<String> {
     env::var(key).ok()
 }
 
-/// Utility function for macros: load .env file
+/// Utility function for macros: return the first of `keys` that is set to a
+/// non-blank value, together with that value
+pub fn get_env_first(keys: &[String]) -> Option<(&str, String)> {
+    keys.iter().find_map(|key| match env::var(key) {
+        Ok(raw) if !raw.trim().is_empty() => Some((key.as_str(), raw)),
+        _ => None,
+    })
+}
+
+/// Expand the `${VAR}` references in `raw`, the value of `key`, against the
+/// environment.
+///
+/// `${VAR:-default}` and `${VAR:?message}` fall back to `default` or fail with
+/// `message` when `VAR` is unset or empty; `${VAR-default}` and `${VAR?message}`
+/// only when it is unset. `$${` stands for a literal `${`. Values taken from
+/// the environment are used as is. Undefined variables and malformed
+/// references are reported as [`CfgError::InterpolationError`].
+pub fn interpolate(key: &str, raw: &str) -> Result<String, CfgError> {
+    Expander::new(&std::collections::HashMap::new()).expand_value(key, raw)
+}
+
+/// Expands `${...}` references, looking variables up in the environment and
+/// then among the raw values of a .env file being loaded
+struct Expander<'a> {
+    file: &'a std::collections::HashMap<String, String>,
+    /// The variables currently being expanded, to detect cycles
+    stack: Vec<String>,
+}
+
+impl<'a> Expander<'a> {
+    fn new(file: &'a std::collections::HashMap<String, String>) -> Self {
+        Expander {
+            file,
+            stack: Vec::new(),
+        }
+    }
+
+    /// Expand `raw`, the value of `key`
+    fn expand_value(&mut self, key: &str, raw: &str) -> Result<String, CfgError> {
+        self.stack = vec![key.to_string()];
+        self.expand(raw)
+            .map_err(|msg| CfgError::InterpolationError {
+                key: key.to_string(),
+                msg,
+            })
+    }
+
+    fn expand(&mut self, raw: &str) -> Result<String, String> {
+        let mut out = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(start) = rest.find('$') {
+            out.push_str(&rest[..start]);
+            let tail = &rest[start..];
+            if let Some(tail) = tail.strip_prefix("$${") {
+                out.push_str("${");
+                rest = tail;
+            } else if let Some(body) = tail.strip_prefix("${") {
+                let end =
+                    closing_brace(body).ok_or_else(|| format!("unterminated `${{` in `{raw}`"))?;
+                out.push_str(&self.expand_reference(&body[..end])?);
+                rest = &body[end + 1..];
+            } else {
+                out.push('$');
+                rest = &tail[1..];
+            }
+        }
+        out.push_str(rest);
+        Ok(out)
+    }
+
+    /// Expand the inside of `${...}`, e.g. `VAR` or `VAR:-default`
+    fn expand_reference(&mut self, reference: &str) -> Result<String, String> {
+        let name_end = reference
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or(reference.len());
+        let (name, op) = reference.split_at(name_end);
+        let invalid = || format!("invalid reference `${{{reference}}}`");
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        // Only values from the .env file still need expanding
+        let (value, from_file) = match env::var(name) {
+            Ok(value) => (Some(value), false),
+            Err(_) => (self.file.get(name).cloned(), true),
+        };
+        let (colon, op) = match op.strip_prefix(':') {
+            Some(op) => (true, op),
+            None => (false, op),
+        };
+        let unset = match &value {
+            Some(value) => colon && value.is_empty(),
+            None => true,
+        };
+        if let Some(default) = op.strip_prefix('-') {
+            if unset {
+                return self.expand(default);
+            }
+        } else if let Some(message) = op.strip_prefix('?') {
+            if unset {
+                return Err(match message {
+                    "" => format!("{name} is not set"),
+                    message => format!("{name}: {message}"),
+                });
+            }
+        } else if colon || !op.is_empty() {
+            return Err(invalid());
+        }
+        let Some(value) = value else {
+            return Err(format!("undefined variable {name}"));
+        };
+        if !from_file {
+            return Ok(value);
+        }
+
+        if self.stack.iter().any(|key| key == name) {
+            return Err(format!(
+                "cyclic reference {} -> {name}",
+                self.stack.join(" -> ")
+            ));
+        }
+        self.stack.push(name.to_string());
+        let value = self.expand(&value)?;
+        self.stack.pop();
+        Ok(value)
+    }
+}
+
+/// The index of the `}` closing a reference, skipping nested `${...}` in defaults
+fn closing_brace(body: &str) -> Option<usize> {
+    let mut depth = 0usize;
+    let mut prev = None;
+    for (i, c) in body.char_indices() {
+        match c {
+            '{' if prev == Some('$') => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// Utility function for macros: load `T` from `{prefix}0_`, `{prefix}1_`, ...
+/// until the first index with no non-blank variable under it
+pub fn load_indexed<T: FromEnv>(prefix: &str, report: &mut LoadReport) -> Result<Vec<T>, CfgError> {
+    indexed_prefixes(prefix)
+        .into_iter()
+        .map(|(index, element)| {
+            T::load_prefixed_with_report(&element, report).map_err(|e| CfgError::IndexedError {
+                prefix: prefix.to_string(),
+                index,
+                source: Box::new(e),
+            })
+        })
+        .collect()
+}
+
+/// Like [`load_indexed`], collecting the errors of every element under `[index]`
+pub fn load_indexed_fields<T: FromEnv>(
+    prefix: &str,
+    report: &mut LoadReport,
+) -> Result<Vec<T>, Vec<FieldError>> {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    for (index, element) in indexed_prefixes(prefix) {
+        match load_section(&format!("[{}]", index), || T::load_fields(&element, report)) {
+            Ok(item) => items.push(item),
+            Err(e) => errors.extend(e),
+        }
+    }
+    if errors.is_empty() {
+        Ok(items)
+    } else {
+        Err(errors)
+    }
+}
+
+/// The index and prefix of every element of an indexed field, up to the first gap
+fn indexed_prefixes(prefix: &str) -> Vec<(usize, String)> {
+    let keys = set_keys_under(prefix);
+    (0..)
+        .map(|index| (index, format!("{}{}_", prefix, index)))
+        .take_while(|(_, element)| keys.iter().any(|key| key.starts_with(element)))
+        .collect()
+}
+
+/// Utility function for macros: load a `T` for every `{prefix}{NAME}_` group
+/// in the environment, keyed by `NAME`.
+///
+/// A group is found from any set variable that ends in one of `T`'s keys; when
+/// several keys match, the longest one wins.
+pub fn load_keyed<K, T, M>(prefix: &str, report: &mut LoadReport) -> Result<M, CfgError>
+where
+    K: std::str::FromStr,
+    <K as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    T: FromEnv,
+    M: EnvMap<K, T>,
+{
+    let mut out = M::default();
+    for name in keyed_names::<T>(prefix) {
+        let group = format!("{}{}_", prefix, name);
+        let item =
+            T::load_prefixed_with_report(&group, report).map_err(|e| CfgError::KeyedError {
+                prefix: prefix.to_string(),
+                name: name.clone(),
+                source: Box::new(e),
+            })?;
+        insert_entry(&mut out, &group, name, item)?;
+    }
+    Ok(out)
+}
+
+/// Like [`load_keyed`], collecting the errors of every group under `[NAME]`
+pub fn load_keyed_fields<K, T, M>(
+    prefix: &str,
+    report: &mut LoadReport,
+) -> Result<M, Vec<FieldError>>
+where
+    K: std::str::FromStr,
+    <K as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    T: FromEnv,
+    M: EnvMap<K, T>,
+{
+    let mut out = M::default();
+    let mut errors = Vec::new();
+    for name in keyed_names::<T>(prefix) {
+        let group = format!("{}{}_", prefix, name);
+        let loaded = load_section(&format!("[{}]", name), || {
+            let item = T::load_fields(&group, report)?;
+            Ok(insert_entry(&mut out, &group, name.clone(), item)?)
+        });
+        if let Err(e) = loaded {
+            errors.extend(e);
+        }
+    }
+    if errors.is_empty() {
+        Ok(out)
+    } else {
+        Err(errors)
+    }
+}
+
+/// The names of every `{prefix}{NAME}_` group holding one of `T`'s keys
+fn keyed_names<T: FromEnv>(prefix: &str) -> std::collections::BTreeSet<String> {
+    let suffixes = T::env_keys();
+    let mut names = std::collections::BTreeSet::new();
+    for key in set_keys_under(prefix) {
+        let rest = &key[prefix.len()..];
+        let name = suffixes
+            .iter()
+            .filter_map(|suffix| rest.strip_suffix(suffix.as_str())?.strip_suffix('_'))
+            .filter(|name| !name.is_empty())
+            .min_by_key(|name| name.len());
+        if let Some(name) = name {
+            names.insert(name.to_string());
+        }
+    }
+    names
+}
+
+/// Parse the group `name` as a map key and insert `item` under it
+fn insert_entry<K, T, M>(out: &mut M, group: &str, name: String, item: T) -> Result<(), CfgError>
+where
+    K: std::str::FromStr,
+    <K as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    M: EnvMap<K, T>,
+{
+    let key = parse_scalar::<K>(group, name.clone())?;
+    if !out.insert_new(key, item) {
+        return Err(CfgError::ParseError {
+            key: group.to_string(),
+            source: Box::new(ParserError(format!("duplicate key `{name}`"))),
+            value: name,
+            ty: std::any::type_name::<M>(),
+        });
+    }
+    Ok(())
+}
+
+/// Utility function for macros: load a single field, reporting its error under `path`
+pub fn load_field<T>(
+    path: &str,
+    load: impl FnOnce() -> Result<T, CfgError>,
+) -> Result<T, Vec<FieldError>> {
+    load().map_err(|error| vec![FieldError::new(path, error)])
+}
+
+/// Utility function for macros: load a nested section, prepending `path` to
+/// the paths of its errors
+pub fn load_section<T>(
+    path: &str,
+    load: impl FnOnce() -> Result<T, Vec<FieldError>>,
+) -> Result<T, Vec<FieldError>> {
+    load().map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|e| FieldError::new(join_path(path, &e.path), e.error))
+            .collect()
+    })
+}
+
+/// Join field paths, e.g. `database` and `url`, or `upstreams` and `[1].host`
+fn join_path(parent: &str, child: &str) -> String {
+    if parent.is_empty() || child.is_empty() || child.starts_with('[') {
+        format!("{}{}", parent, child)
+    } else {
+        format!("{}.{}", parent, child)
+    }
+}
+
+/// Utility function for macros: whether the toggle `key` of an optional nested
+/// section is set to true, see [`parse_bool`]. An unset or blank toggle
+/// disables the section.
+pub fn section_enabled(key: &str) -> Result<bool, CfgError> {
+    match env::var(key) {
+        Ok(raw) if !raw.trim().is_empty() => parse_with(key, raw, parse_bool),
+        _ => Ok(false),
+    }
+}
+
+/// Utility function for macros: whether any of a section's `keys` is set to a
+/// non-blank value under `prefix`
+pub fn section_present(prefix: &str, keys: &[String]) -> bool {
+    keys.iter()
+        .any(|key| env::var(format!("{}{}", prefix, key)).is_ok_and(|raw| !raw.trim().is_empty()))
+}
+
+/// Every variable starting with `prefix` that is set to a non-blank value
+fn set_keys_under(prefix: &str) -> Vec<String> {
+    env::vars_os()
+        .filter_map(|(key, value)| {
+            let key = key.into_string().ok()?;
+            let set = value.to_str().is_some_and(|v| !v.trim().is_empty());
+            (set && key.starts_with(prefix)).then_some(key)
+        })
+        .collect()
+}
+
+/// Utility function for macros: load .env file.
+///
+/// `${VAR}` references in its values are expanded (see [`interpolate`])
+/// against the environment and the file's other values before any variable
+/// is set, so nothing is set if one of them fails. Variables that are already
+/// set are left unchanged.
 pub fn load_env_file(env_path: &std::path::Path) -> Result<(), CfgError> {
+    let load_error = |e| CfgError::LoadError {
+        msg: "failed to load .env file",
+        source: Box::new(e),
+    };
     // Try to load .env file if it exists, but don't fail if it doesn't
-    match dotenvy::from_path(env_path) {
-        Ok(_) => Ok(()),
-        Err(dotenvy::Error::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
-        Err(e) => Err(CfgError::LoadError {
-            msg: "failed to load .env file",
-            source: Box::new(e),
-        }),
+    let content = match std::fs::read_to_string(env_path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(load_error(dotenvy::Error::Io(e))),
+    };
+    let mut entries = Vec::new();
+    let mut file = std::collections::HashMap::new();
+    for entry in dotenvy::from_read_iter(escape_references(&content).as_bytes()) {
+        let (key, raw) = entry.map_err(load_error)?;
+        // The first definition wins, as with dotenvy
+        if let std::collections::hash_map::Entry::Vacant(e) = file.entry(key.clone()) {
+            e.insert(raw);
+            entries.push(key);
+        }
     }
+
+    let mut expander = Expander::new(&file);
+    let mut values = Vec::new();
+    for key in entries {
+        if env::var_os(&key).is_none() {
+            let value = expander.expand_value(&key, &file[&key])?;
+            values.push((key, value));
+        }
+    }
+    for (key, value) in values {
+        // SAFETY: as in dotenvy, loading the .env file is expected to happen
+        // before other threads read or write the environment
+        unsafe { env::set_var(key, value) };
+    }
+    Ok(())
+}
+
+/// Keep the `${...}` references in .env `content` away from dotenvy's own
+/// substitution. Outside single quotes `${` and `$${` are escaped with
+/// backslashes so they reach the expansion; inside, `${` becomes `$${` so the
+/// value stays literal as in a shell.
+fn escape_references(content: &str) -> String {
+    let mut out = String::with_capacity(content.len());
+    let (mut single, mut double, mut escaped, mut comment) = (false, false, false, false);
+    let mut prev = '\n';
+    for (i, c) in content.char_indices() {
+        if c == '\n' {
+            escaped = false;
+            comment = false;
+        } else if comment {
+            // Comments are copied as is
+        } else if escaped {
+            escaped = false;
+        } else if single {
+            single = c != '\'';
+            if content[i..].starts_with("${") {
+                out.push('$');
+            }
+        } else {
+            match c {
+                '\\' => escaped = true,
+                '\'' if !double => single = true,
+                '"' => double = !double,
+                '#' if !double && prev.is_whitespace() => comment = true,
+                '$' if content[i..].starts_with("${") || content[i..].starts_with("$${") => {
+                    out.push('\\')
+                }
+                _ => {}
+            }
+        }
+        out.push(c);
+        prev = c;
+    }
+    out
 }
 
 /// Load .env from multiple paths (any iterable), return on first success.
@@ -107,12 +715,12 @@ where
 }
 
 /// Utility function for macros: parse string to T
-pub fn parse_scalar<T: std::str::FromStr>(key: &'static str, raw: String) -> Result<T, CfgError>
+pub fn parse_scalar<T: std::str::FromStr>(key: &str, raw: String) -> Result<T, CfgError>
 where
     <T as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
 {
     raw.parse::<T>().map_err(|e| CfgError::ParseError {
-        key,
+        key: key.to_string(),
         value: raw,
         ty: std::any::type_name::<T>(),
         source: Box::new(e),
@@ -121,23 +729,1693 @@ where
 
 /// Split string and parse each part to `Vec<T>`
 pub fn parse_vec<T: std::str::FromStr>(
-    key: &'static str,
+    key: &str,
     raw: String,
-    sep: &'static str,
+    sep: &str,
 ) -> Result<Vec<T>, CfgError>
 where
     <T as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
 {
-    if sep.is_empty() {
-        return Ok(Vec::new());
+    split_parts(&raw, sep)
+        .map(|s| parse_scalar::<T>(key, s.to_string()))
+        .collect()
+}
+
+/// Utility function for macros: parse string to T with a custom parser function.
+///
+/// Unlike [`parse_scalar`], `T` does not need to implement `FromStr` and the
+/// parser's error only needs to implement `Display`.
+pub fn parse_with<T, E, F>(key: &str, raw: String, parser: F) -> Result<T, CfgError>
+where
+    F: FnOnce(&str) -> Result<T, E>,
+    E: std::fmt::Display,
+{
+    match parser(&raw) {
+        Ok(value) => Ok(value),
+        Err(e) => Err(CfgError::ParseError {
+            key: key.to_string(),
+            ty: std::any::type_name::<T>(),
+            source: Box::new(ParserError(e.to_string())),
+            value: raw,
+        }),
+    }
+}
+
+/// Spellings [`parse_bool`] accepts for `true`, compared case-insensitively
+pub const TRUTHY: &[&str] = &["true", "t", "yes", "y", "on", "1"];
+
+/// Spellings [`parse_bool`] accepts for `false`, compared case-insensitively
+pub const FALSY: &[&str] = &["false", "f", "no", "n", "off", "0"];
+
+/// Error returned by [`parse_bool`], listing the accepted values
+#[derive(Debug)]
+pub struct ParseBoolError {
+    truthy: Vec<String>,
+    falsy: Vec<String>,
+}
+
+impl std::fmt::Display for ParseBoolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "expected one of {} for true or {} for false (case-insensitive)",
+            self.truthy.join(", "),
+            self.falsy.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ParseBoolError {}
+
+/// Parse a boolean from any of the [`TRUTHY`] or [`FALSY`] spellings, such as
+/// `1`, `yes`, `on`, `TRUE` or `off`
+pub fn parse_bool(s: &str) -> Result<bool, ParseBoolError> {
+    parse_bool_with(s, TRUTHY, FALSY)
+}
+
+/// Parse a boolean from custom sets of `truthy` and `falsy` spellings,
+/// compared case-insensitively after trimming
+pub fn parse_bool_with(s: &str, truthy: &[&str], falsy: &[&str]) -> Result<bool, ParseBoolError> {
+    let s = s.trim();
+    if truthy.iter().any(|t| t.eq_ignore_ascii_case(s)) {
+        Ok(true)
+    } else if falsy.iter().any(|f| f.eq_ignore_ascii_case(s)) {
+        Ok(false)
+    } else {
+        Err(ParseBoolError {
+            truthy: truthy.iter().map(|t| t.to_string()).collect(),
+            falsy: falsy.iter().map(|f| f.to_string()).collect(),
+        })
+    }
+}
+
+/// Error returned by [`parse_duration`], explaining the accepted syntax
+#[derive(Debug)]
+pub struct ParseDurationError(String);
+
+impl std::fmt::Display for ParseDurationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}; expected a number followed by a unit ({}), e.g. `30s`, `1m30s` or `250ms`",
+            self.0,
+            DURATION_UNITS
+                .iter()
+                .map(|(unit, _)| *unit)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+}
+
+impl std::error::Error for ParseDurationError {}
+
+/// Duration units and their length in nanoseconds
+const DURATION_UNITS: &[(&str, u128)] = &[
+    ("ns", 1),
+    ("us", 1_000),
+    ("ms", 1_000_000),
+    ("s", 1_000_000_000),
+    ("m", 60 * 1_000_000_000),
+    ("h", 60 * 60 * 1_000_000_000),
+    ("d", 24 * 60 * 60 * 1_000_000_000),
+];
+
+/// Parse a duration such as `30s`, `1m30s`, `250ms`, `1.5h` or `2h`.
+///
+/// Each number is followed by one of `ns`, `us`, `ms`, `s`, `m`, `h` or `d`.
+/// A bare number such as `30` is read in `unit`, and rejected without one
+/// unless it is `0`.
+pub fn parse_duration(
+    s: &str,
+    unit: Option<&str>,
+) -> Result<std::time::Duration, ParseDurationError> {
+    let s = s.trim();
+    if s.is_empty() {
+        return Err(ParseDurationError("empty duration".to_string()));
+    }
+    let is_number = |c: char| c.is_ascii_digit() || c == '.';
+    if s.chars().all(is_number) {
+        return match unit {
+            Some(unit) => duration_part(s, unit),
+            // Zero is the same in every unit
+            None if s.chars().all(|c| c == '0') => Ok(std::time::Duration::ZERO),
+            None => Err(ParseDurationError(format!("missing unit after `{s}`"))),
+        };
+    }
+    let mut total = std::time::Duration::ZERO;
+    let mut rest = s;
+    while !rest.is_empty() {
+        let number_end = rest.find(|c| !is_number(c)).unwrap_or(rest.len());
+        if number_end == 0 {
+            return Err(ParseDurationError(format!("expected a number at `{rest}`")));
+        }
+        let (number, tail) = rest.split_at(number_end);
+        let unit_end = tail.find(is_number).unwrap_or(tail.len());
+        let (unit, tail) = tail.split_at(unit_end);
+        total = total
+            .checked_add(duration_part(number, unit.trim())?)
+            .ok_or_else(|| ParseDurationError("duration is too large".to_string()))?;
+        rest = tail;
+    }
+    Ok(total)
+}
+
+/// Parse a single `number` of `unit`, e.g. `30` and `s`
+fn duration_part(number: &str, unit: &str) -> Result<std::time::Duration, ParseDurationError> {
+    let Some(&(_, nanos)) = DURATION_UNITS.iter().find(|(name, _)| *name == unit) else {
+        return Err(match unit {
+            "" => ParseDurationError(format!("missing unit after `{number}`")),
+            unit => ParseDurationError(format!("unknown unit `{unit}`")),
+        });
+    };
+    let invalid = || ParseDurationError(format!("invalid number `{number}`"));
+    let too_large = || ParseDurationError("duration is too large".to_string());
+    let not_whole = || {
+        ParseDurationError(format!(
+            "`{number}{unit}` is not a whole number of nanoseconds"
+        ))
+    };
+    let total = scale_decimal(number, nanos).map_err(|e| match e {
+        ScaleError::Invalid => invalid(),
+        ScaleError::TooLarge => too_large(),
+        ScaleError::NotWhole => not_whole(),
+    })?;
+    let secs = u64::try_from(total / 1_000_000_000).map_err(|_| too_large())?;
+    Ok(std::time::Duration::new(
+        secs,
+        (total % 1_000_000_000) as u32,
+    ))
+}
+
+/// Error returned by [`parse_bytes`], explaining the accepted syntax
+#[derive(Debug)]
+pub struct ParseBytesError(String);
+
+impl std::fmt::Display for ParseBytesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}; expected a byte count with an optional SI (kB, MB, GB, ...) or IEC \
+             (KiB, MiB, GiB, ...) unit, e.g. `512`, `512k`, `10MiB` or `1.5GB`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for ParseBytesError {}
+
+/// Parse a byte size such as `512`, `512k`, `10MiB` or `1.5GB` into an integer.
+///
+/// SI units (`k`/`kB`, `M`/`MB`, ... up to `E`/`EB`) are powers of 1000 and IEC
+/// units (`Ki`/`KiB`, `Mi`/`MiB`, ... up to `Ei`/`EiB`) powers of 1024. Units
+/// are case-insensitive. A size too large for `T` or with a fraction of a
+/// byte is rejected.
+pub fn parse_bytes<T: TryFrom<u128>>(s: &str) -> Result<T, ParseBytesError> {
+    let s = s.trim();
+    let number_end = s
+        .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+        .unwrap_or(s.len());
+    let (number, unit) = s.split_at(number_end);
+    if number.is_empty() {
+        return Err(ParseBytesError(format!("invalid byte size `{s}`")));
+    }
+    let unit = unit.trim().to_ascii_lowercase();
+    let unit = unit.strip_suffix('b').unwrap_or(&unit);
+    let (prefix, base) = match unit.strip_suffix('i') {
+        Some(prefix) if !prefix.is_empty() => (prefix, 1024u128),
+        _ => (unit, 1000u128),
+    };
+    let Some(power) = ["", "k", "m", "g", "t", "p", "e"]
+        .iter()
+        .position(|p| *p == prefix)
+    else {
+        return Err(ParseBytesError(format!("unknown unit in `{s}`")));
+    };
+    let too_large = || {
+        ParseBytesError(format!(
+            "`{s}` is too large for {}",
+            std::any::type_name::<T>()
+        ))
+    };
+    let bytes = scale_decimal(number, base.pow(power as u32)).map_err(|e| match e {
+        ScaleError::Invalid => ParseBytesError(format!("invalid number `{number}`")),
+        ScaleError::TooLarge => too_large(),
+        ScaleError::NotWhole => ParseBytesError(format!("`{s}` is not a whole number of bytes")),
+    })?;
+    T::try_from(bytes).map_err(|_| too_large())
+}
+
+/// Why [`scale_decimal`] failed
+enum ScaleError {
+    /// Not a decimal number such as `42` or `1.5`
+    Invalid,
+    /// The product does not fit in a `u128`
+    TooLarge,
+    /// The product has a fractional part
+    NotWhole,
+}
+
+/// Multiply the decimal `number`, e.g. `1.5`, by `multiplier` exactly
+fn scale_decimal(number: &str, multiplier: u128) -> Result<u128, ScaleError> {
+    let (whole, fraction) = number.split_once('.').unwrap_or((number, ""));
+    if whole.is_empty() && fraction.is_empty() || fraction.contains('.') {
+        return Err(ScaleError::Invalid);
+    }
+    let whole: u128 = match whole {
+        "" => 0,
+        whole => whole.parse().map_err(|_| ScaleError::TooLarge)?,
+    };
+    let whole = whole.checked_mul(multiplier).ok_or(ScaleError::TooLarge)?;
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.is_empty() {
+        return Ok(whole);
+    }
+
+    // The fraction is digits / 10^len. Cancel the factors of 2 and 5 that
+    // `multiplier` shares with 10^len; the product is whole only if what
+    // remains of 10^len divides the digits.
+    let (mut multiplier, mut twos, mut fives) =
+        (multiplier, fraction.len() as u32, fraction.len() as u32);
+    while twos > 0 && multiplier.is_multiple_of(2) {
+        multiplier /= 2;
+        twos -= 1;
+    }
+    while fives > 0 && multiplier.is_multiple_of(5) {
+        multiplier /= 5;
+        fives -= 1;
+    }
+    // More significant digits than a u128 holds are treated as not whole
+    let digits: u128 = fraction.parse().map_err(|_| ScaleError::NotWhole)?;
+    // A divisor too large for a u128 exceeds the nonzero digits
+    let divisor = 2u128
+        .checked_pow(twos)
+        .zip(5u128.checked_pow(fives))
+        .and_then(|(twos, fives)| twos.checked_mul(fives))
+        .ok_or(ScaleError::NotWhole)?;
+    if !digits.is_multiple_of(divisor) {
+        return Err(ScaleError::NotWhole);
+    }
+    (digits / divisor)
+        .checked_mul(multiplier)
+        .and_then(|part| whole.checked_add(part))
+        .ok_or(ScaleError::TooLarge)
+}
+
+/// Integer types accepted by [`parse_int`]
+pub trait EnvInt: Sized {
+    /// Whether the type has negative values
+    const SIGNED: bool;
+    fn from_str_radix(src: &str, radix: u32) -> Result<Self, std::num::ParseIntError>;
+}
+
+macro_rules! impl_env_int {
+    ($($ty:ty)*) => {
+        $(
+            impl EnvInt for $ty {
+                const SIGNED: bool = <$ty>::MIN != 0;
+                fn from_str_radix(src: &str, radix: u32) -> Result<Self, std::num::ParseIntError> {
+                    <$ty>::from_str_radix(src, radix)
+                }
+            }
+        )*
+    };
+}
+
+impl_env_int!(i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize);
+
+/// Error returned by [`parse_int`], explaining the accepted syntax
+#[derive(Debug)]
+pub struct ParseIntegerError {
+    msg: String,
+    /// Whether the target type has negative values, to show one as an example
+    signed: bool,
+}
+
+impl ParseIntegerError {
+    fn new<T: EnvInt>(msg: String) -> Self {
+        ParseIntegerError {
+            msg,
+            signed: T::SIGNED,
+        }
+    }
+}
+
+impl std::fmt::Display for ParseIntegerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let negative = if self.signed { "`-7`, " } else { "" };
+        write!(
+            f,
+            "{}; expected an integer such as `42`, {}`1_000_000`, `0xFF00`, `0o644` or `0b1010`",
+            self.msg, negative
+        )
+    }
+}
+
+impl std::error::Error for ParseIntegerError {}
+
+/// Parse an integer with an optional `0x`, `0o` or `0b` radix prefix and `_`
+/// digit separators, e.g. `0o644`, `0xFF00` or `1_000_000`.
+///
+/// A leading zero alone does not mean octal, so `0644` is `644`. Values out of
+/// the range of `T` are rejected.
+pub fn parse_int<T: EnvInt>(s: &str) -> Result<T, ParseIntegerError> {
+    let s = s.trim();
+    let (sign, unsigned) = match s.strip_prefix(['+', '-']) {
+        Some(rest) => (&s[..1], rest),
+        None => ("", s),
+    };
+    let prefix = unsigned.get(..2).map(str::to_ascii_lowercase);
+    let (radix, digits) = match prefix.as_deref() {
+        Some("0x") => (16, &unsigned[2..]),
+        Some("0o") => (8, &unsigned[2..]),
+        Some("0b") => (2, &unsigned[2..]),
+        _ => (10, unsigned),
+    };
+    let ty = std::any::type_name::<T>();
+    let invalid = || ParseIntegerError::new::<T>(format!("invalid integer `{s}`"));
+    let too_small = || ParseIntegerError::new::<T>(format!("`{s}` is too small for {ty}"));
+    // Separators go between digits, as in `1_000`
+    if digits.starts_with('_') || digits.ends_with('_') {
+        return Err(invalid());
+    }
+    let digits: String = digits.chars().filter(|c| *c != '_').collect();
+    // A sign belongs before the radix prefix, not after it
+    if digits.starts_with(['+', '-']) {
+        return Err(invalid());
+    }
+    if sign == "-" && !T::SIGNED {
+        // Unsigned types reject the sign itself, so judge the magnitude: `-0`
+        // is zero and any other valid number is below the range
+        return match T::from_str_radix(&digits, radix) {
+            Ok(zero) if digits.chars().all(|c| c == '0') => Ok(zero),
+            Ok(_) => Err(too_small()),
+            Err(e) if *e.kind() == std::num::IntErrorKind::PosOverflow => Err(too_small()),
+            Err(_) => Err(invalid()),
+        };
+    }
+    T::from_str_radix(&format!("{sign}{digits}"), radix).map_err(|e| match e.kind() {
+        std::num::IntErrorKind::PosOverflow => {
+            ParseIntegerError::new::<T>(format!("`{s}` is too large for {ty}"))
+        }
+        std::num::IntErrorKind::NegOverflow => too_small(),
+        _ => invalid(),
+    })
+}
+
+/// Split string and parse each part to `Vec<T>` with a custom parser function
+pub fn parse_vec_with<T, E, F>(
+    key: &str,
+    raw: String,
+    sep: &str,
+    parser: F,
+) -> Result<Vec<T>, CfgError>
+where
+    F: Fn(&str) -> Result<T, E>,
+    E: std::fmt::Display,
+{
+    split_parts(&raw, sep)
+        .map(|s| parse_with(key, s.to_string(), &parser))
+        .collect()
+}
+
+/// Set types that [`parse_set`] can fill
+pub trait EnvSet<T>: Default {
+    /// Insert `value` unless it is already present, returning whether it was inserted
+    fn insert_new(&mut self, value: T) -> bool;
+}
+
+impl<T, S> EnvSet<T> for std::collections::HashSet<T, S>
+where
+    T: Eq + std::hash::Hash,
+    S: std::hash::BuildHasher + Default,
+{
+    fn insert_new(&mut self, value: T) -> bool {
+        self.insert(value)
+    }
+}
+
+impl<T: Ord> EnvSet<T> for std::collections::BTreeSet<T> {
+    fn insert_new(&mut self, value: T) -> bool {
+        self.insert(value)
+    }
+}
+
+/// Split string and parse each part into a set.
+///
+/// A value given twice is an error unless `dedup` is set, in which case
+/// duplicates are dropped.
+pub fn parse_set<T, S>(key: &str, raw: String, sep: &str, dedup: bool) -> Result<S, CfgError>
+where
+    T: std::str::FromStr,
+    <T as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    S: EnvSet<T>,
+{
+    collect_set(key, &raw, sep, dedup, |s| {
+        parse_scalar::<T>(key, s.to_string())
+    })
+}
+
+/// Split string and parse each part into a set with a custom parser function
+pub fn parse_set_with<T, S, E, F>(
+    key: &str,
+    raw: String,
+    sep: &str,
+    dedup: bool,
+    parser: F,
+) -> Result<S, CfgError>
+where
+    S: EnvSet<T>,
+    F: Fn(&str) -> Result<T, E>,
+    E: std::fmt::Display,
+{
+    collect_set(key, &raw, sep, dedup, |s| {
+        parse_with(key, s.to_string(), &parser)
+    })
+}
+
+fn collect_set<T, S: EnvSet<T>>(
+    key: &str,
+    raw: &str,
+    sep: &str,
+    dedup: bool,
+    mut parse: impl FnMut(&str) -> Result<T, CfgError>,
+) -> Result<S, CfgError> {
+    let mut out = S::default();
+    for part in split_parts(raw, sep) {
+        if !out.insert_new(parse(part)?) && !dedup {
+            return Err(CfgError::ParseError {
+                key: key.to_string(),
+                value: raw.to_string(),
+                ty: std::any::type_name::<S>(),
+                source: Box::new(ParserError(format!("duplicate value `{part}`"))),
+            });
+        }
+    }
+    Ok(out)
+}
+
+/// Split string and parse exactly `N` parts into an array
+pub fn parse_array<T, const N: usize>(key: &str, raw: String, sep: &str) -> Result<[T; N], CfgError>
+where
+    T: std::str::FromStr,
+    <T as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+{
+    let values = parse_vec::<T>(key, raw.clone(), sep)?;
+    into_array(key, raw, values)
+}
+
+/// Split string and parse exactly `N` parts into an array with a custom parser function
+pub fn parse_array_with<T, E, F, const N: usize>(
+    key: &str,
+    raw: String,
+    sep: &str,
+    parser: F,
+) -> Result<[T; N], CfgError>
+where
+    F: Fn(&str) -> Result<T, E>,
+    E: std::fmt::Display,
+{
+    let values = parse_vec_with(key, raw.clone(), sep, parser)?;
+    into_array(key, raw, values)
+}
+
+fn into_array<T, const N: usize>(
+    key: &str,
+    raw: String,
+    values: Vec<T>,
+) -> Result<[T; N], CfgError> {
+    let found = values.len();
+    values.try_into().map_err(|_| CfgError::ParseError {
+        key: key.to_string(),
+        value: raw,
+        ty: std::any::type_name::<[T; N]>(),
+        source: Box::new(ParserError(format!(
+            "expected exactly {N} values, found {found}"
+        ))),
+    })
+}
+
+/// The trimmed, non-empty parts of `raw` split on `sep`; nothing if `sep` is empty
+fn split_parts<'a>(raw: &'a str, sep: &'a str) -> impl Iterator<Item = &'a str> {
+    raw.split(sep)
+        .filter(move |_| !sep.is_empty())
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+}
+
+/// Map types that [`parse_map`] can fill, rejecting duplicate keys
+pub trait EnvMap<K, V>: Default {
+    /// Insert `key` unless it is already present, returning whether it was inserted
+    fn insert_new(&mut self, key: K, value: V) -> bool;
+}
+
+impl<K, V, S> EnvMap<K, V> for std::collections::HashMap<K, V, S>
+where
+    K: Eq + std::hash::Hash,
+    S: std::hash::BuildHasher + Default,
+{
+    fn insert_new(&mut self, key: K, value: V) -> bool {
+        match self.entry(key) {
+            std::collections::hash_map::Entry::Occupied(_) => false,
+            std::collections::hash_map::Entry::Vacant(e) => {
+                e.insert(value);
+                true
+            }
+        }
+    }
+}
+
+impl<K: Ord, V> EnvMap<K, V> for std::collections::BTreeMap<K, V> {
+    fn insert_new(&mut self, key: K, value: V) -> bool {
+        match self.entry(key) {
+            std::collections::btree_map::Entry::Occupied(_) => false,
+            std::collections::btree_map::Entry::Vacant(e) => {
+                e.insert(value);
+                true
+            }
+        }
+    }
+}
+
+/// Split string into `key<kv_sep>value` entries and parse them into a map.
+///
+/// Errors name the offending entry, and a key that appears twice is rejected.
+pub fn parse_map<K, V, M>(key: &str, raw: String, sep: &str, kv_sep: &str) -> Result<M, CfgError>
+where
+    K: std::str::FromStr,
+    <K as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    V: std::str::FromStr,
+    <V as std::str::FromStr>::Err: std::error::Error + Send + Sync + 'static,
+    M: EnvMap<K, V>,
+{
+    collect_map(
+        key,
+        raw,
+        sep,
+        kv_sep,
+        |k| k.parse::<K>().map_err(|e| Box::new(e) as _),
+        |v| v.parse::<V>().map_err(|e| Box::new(e) as _),
+    )
+}
+
+/// Like [`parse_map`], parsing keys and values with custom parser functions
+pub fn parse_map_with<K, V, M, EK, EV, FK, FV>(
+    key: &str,
+    raw: String,
+    sep: &str,
+    kv_sep: &str,
+    parse_key: FK,
+    parse_value: FV,
+) -> Result<M, CfgError>
+where
+    M: EnvMap<K, V>,
+    EK: std::fmt::Display,
+    EV: std::fmt::Display,
+    FK: Fn(&str) -> Result<K, EK>,
+    FV: Fn(&str) -> Result<V, EV>,
+{
+    collect_map(
+        key,
+        raw,
+        sep,
+        kv_sep,
+        |k| parse_key(k).map_err(|e| Box::new(ParserError(e.to_string())) as _),
+        |v| parse_value(v).map_err(|e| Box::new(ParserError(e.to_string())) as _),
+    )
+}
+
+/// Parse every `key<kv_sep>value` entry of `raw` into `M`
+fn collect_map<K, V, M: EnvMap<K, V>>(
+    key: &str,
+    raw: String,
+    sep: &str,
+    kv_sep: &str,
+    parse_key: impl Fn(&str) -> Result<K, Box<dyn std::error::Error + Send + Sync>>,
+    parse_value: impl Fn(&str) -> Result<V, Box<dyn std::error::Error + Send + Sync>>,
+) -> Result<M, CfgError> {
+    let mut out = M::default();
+    if sep.is_empty() || kv_sep.is_empty() {
+        return Ok(out);
     }
-    let mut out = Vec::new();
     for part in raw.split(sep) {
-        let s = part.trim().to_string();
-        if s.is_empty() {
+        let entry = part.trim();
+        if entry.is_empty() {
             continue;
         }
-        out.push(parse_scalar::<T>(key, s)?);
+        let entry_error =
+            |ty, source: Box<dyn std::error::Error + Send + Sync>| CfgError::ParseError {
+                key: key.to_string(),
+                value: entry.to_string(),
+                ty,
+                source,
+            };
+        let Some((k, v)) = entry.split_once(kv_sep) else {
+            return Err(entry_error(
+                std::any::type_name::<M>(),
+                Box::new(ParserError(format!(
+                    "expected an entry of the form `key{kv_sep}value`"
+                ))),
+            ));
+        };
+        let raw_key = k.trim();
+        let k = parse_key(raw_key).map_err(|e| entry_error(std::any::type_name::<K>(), e))?;
+        let v = parse_value(v.trim()).map_err(|e| entry_error(std::any::type_name::<V>(), e))?;
+        if !out.insert_new(k, v) {
+            return Err(entry_error(
+                std::any::type_name::<M>(),
+                Box::new(ParserError(format!("duplicate key `{raw_key}`"))),
+            ));
+        }
     }
     Ok(out)
 }
+
+/// Types whose length can be checked with `#[env(len(min = .., max = ..))]`
+pub trait EnvLen {
+    /// The length of the value; characters for strings, elements for collections
+    fn env_len(&self) -> usize;
+}
+
+impl EnvLen for str {
+    fn env_len(&self) -> usize {
+        self.chars().count()
+    }
+}
+
+impl EnvLen for String {
+    fn env_len(&self) -> usize {
+        self.as_str().env_len()
+    }
+}
+
+impl<T: EnvLen + ?Sized> EnvLen for Box<T> {
+    fn env_len(&self) -> usize {
+        T::env_len(self)
+    }
+}
+
+macro_rules! impl_env_len {
+    ($($ty:ty => [$($generics:tt)*]),* $(,)?) => {
+        $(
+            impl<$($generics)*> EnvLen for $ty {
+                fn env_len(&self) -> usize {
+                    self.len()
+                }
+            }
+        )*
+    };
+}
+
+impl_env_len! {
+    [T] => [T],
+    [T; N] => [T, const N: usize],
+    Vec<T> => [T],
+    std::collections::VecDeque<T> => [T],
+    std::collections::HashSet<T, S> => [T, S],
+    std::collections::BTreeSet<T> => [T],
+    std::collections::HashMap<K, V, S> => [K, V, S],
+    std::collections::BTreeMap<K, V> => [K, V],
+}
+
+fn validation_error(key: &str, raw: &str, rule: &str) -> CfgError {
+    CfgError::ValidationError {
+        key: key.to_string(),
+        value: raw.to_string(),
+        rule: rule.to_string(),
+    }
+}
+
+/// Utility function for macros: check `value` lies in `range`
+pub fn check_range<T, R>(
+    key: &str,
+    raw: &str,
+    value: &T,
+    range: R,
+    rule: &str,
+) -> Result<(), CfgError>
+where
+    T: PartialOrd,
+    R: std::ops::RangeBounds<T>,
+{
+    if range.contains(value) {
+        Ok(())
+    } else {
+        Err(validation_error(key, raw, rule))
+    }
+}
+
+/// Utility function for macros: check the length of `value` is within `min..=max`
+pub fn check_len<T: EnvLen + ?Sized>(
+    key: &str,
+    raw: &str,
+    value: &T,
+    min: Option<usize>,
+    max: Option<usize>,
+    rule: &str,
+) -> Result<(), CfgError> {
+    let len = value.env_len();
+    if min.is_some_and(|min| len < min) || max.is_some_and(|max| len > max) {
+        return Err(validation_error(key, raw, rule));
+    }
+    Ok(())
+}
+
+/// Utility function for macros: check `value` equals one of `options`
+pub fn check_one_of<T, O>(
+    key: &str,
+    raw: &str,
+    value: &T,
+    options: &[O],
+    rule: &str,
+) -> Result<(), CfgError>
+where
+    T: PartialEq<O>,
+{
+    if options.iter().any(|option| value == option) {
+        Ok(())
+    } else {
+        Err(validation_error(key, raw, rule))
+    }
+}
+
+/// Utility function for macros: check `value` matches the regular expression `pattern`
+#[cfg(feature = "regex")]
+pub fn check_regex(
+    key: &str,
+    raw: &str,
+    value: &str,
+    pattern: &str,
+    rule: &str,
+) -> Result<(), CfgError> {
+    let regex = regex::Regex::new(pattern).map_err(|e| CfgError::LoadError {
+        msg: "invalid regex pattern",
+        source: Box::new(e),
+    })?;
+    if regex.is_match(value) {
+        Ok(())
+    } else {
+        Err(validation_error(key, raw, rule))
+    }
+}
+
+/// Whether `key` is set to a non-blank value
+fn is_set(key: &str) -> bool {
+    env::var(key).is_ok_and(|raw| !raw.trim().is_empty())
+}
+
+/// Utility function for macros: check every key in `others` is set, as `key` is
+pub fn check_requires(key: &str, others: &[String]) -> Result<(), CfgError> {
+    match others.iter().find(|other| !is_set(other)) {
+        Some(other) => Err(CfgError::ConstraintError {
+            key: key.to_string(),
+            rule: format!("requires {} to be set", other),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Utility function for macros: check no key in `others` is set, as `key` is
+pub fn check_conflicts(key: &str, others: &[String]) -> Result<(), CfgError> {
+    match others.iter().find(|other| is_set(other)) {
+        Some(other) => Err(CfgError::ConstraintError {
+            key: key.to_string(),
+            rule: format!("conflicts with {}", other),
+        }),
+        None => Ok(()),
+    }
+}
+
+/// Utility function for macros: fail for the unset `key` if `condition` is set
+/// to `value` (compared after trimming, ignoring ASCII case)
+pub fn check_required_if(key: &str, condition: &str, value: &str) -> Result<(), CfgError> {
+    match env::var(condition) {
+        Ok(raw) if raw.trim().eq_ignore_ascii_case(value) => Err(CfgError::ConstraintError {
+            key: key.to_string(),
+            rule: format!("is required when {} is `{}`", condition, value),
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// Utility function for macros: run the struct-level `validate` function on
+/// the loaded `value`
+pub fn run_validate<T, E, F>(value: &T, validate: F) -> Result<(), CfgError>
+where
+    E: std::fmt::Display,
+    F: FnOnce(&T) -> Result<(), E>,
+{
+    validate(value).map_err(|e| CfgError::StructValidationError {
+        ty: std::any::type_name::<T>(),
+        source: Box::new(ParserError(e.to_string())),
+    })
+}
+
+/// Error from a custom parser or validate function, keeping only its message
+#[derive(Debug)]
+struct ParserError(String);
+
+impl std::fmt::Display for ParserError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParserError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Set `vars` for a test. Every test uses its own key prefix, so tests
+    /// running in parallel never see each other's variables.
+    fn set_vars(vars: &[(&str, &str)]) {
+        for (key, value) in vars {
+            // SAFETY: other tests read and write the environment concurrently,
+            // including `env::vars_os` in `keyed_names` and `env::set_var` in
+            // `load_env_file`, but only through std, which serializes every
+            // access with its own lock. Nothing in this binary calls the C
+            // library's `getenv` or `setenv` directly.
+            unsafe { env::set_var(key, value) };
+        }
+    }
+
+    /// A nested struct with the keys `URL`, `SIZE` and `POOL_SIZE`
+    struct Group;
+
+    impl FromEnv for Group {
+        fn load(_env_path: &std::path::Path) -> Result<Self, CfgError> {
+            Ok(Group)
+        }
+        fn load_prefixed_with_report(
+            _prefix: &str,
+            _report: &mut LoadReport,
+        ) -> Result<Self, CfgError> {
+            Ok(Group)
+        }
+        fn env_keys() -> Vec<String> {
+            vec!["URL".into(), "SIZE".into(), "POOL_SIZE".into()]
+        }
+        fn load_iter<I, P>(_paths: I) -> Result<Self, CfgError>
+        where
+            I: IntoIterator<Item = P>,
+            P: AsRef<std::path::Path>,
+        {
+            Ok(Group)
+        }
+    }
+
+    #[test]
+    fn keyed_names_finds_groups_with_underscores() {
+        set_vars(&[
+            ("KEYED_A_PRIMARY_URL", "x"),
+            ("KEYED_A_READ_REPLICA_URL", "x"),
+            ("KEYED_A_READ_REPLICA_SIZE", "1"),
+        ]);
+        let names: Vec<_> = keyed_names::<Group>("KEYED_A_").into_iter().collect();
+        assert_eq!(names, ["PRIMARY", "READ_REPLICA"]);
+    }
+
+    #[test]
+    fn keyed_names_prefers_the_longest_key() {
+        // POOL_SIZE wins over SIZE, so the group is `CACHE` and not `CACHE_POOL`
+        set_vars(&[("KEYED_B_CACHE_POOL_SIZE", "4")]);
+        let names: Vec<_> = keyed_names::<Group>("KEYED_B_").into_iter().collect();
+        assert_eq!(names, ["CACHE"]);
+    }
+
+    #[test]
+    fn keyed_names_skips_blank_unknown_and_unnamed_keys() {
+        set_vars(&[
+            ("KEYED_C_BLANK_URL", "  "),
+            ("KEYED_C_OTHER_HOST", "x"),
+            ("KEYED_C_URL", "x"),
+            ("KEYED_C_X_URLS", "x"),
+        ]);
+        assert!(keyed_names::<Group>("KEYED_C_").is_empty());
+    }
+
+    #[test]
+    fn parse_bool_ignores_case_and_whitespace() {
+        for raw in ["true", "TRUE", " Yes ", "y", "On", "1", "t\n"] {
+            assert!(parse_bool(raw).unwrap(), "{raw:?}");
+        }
+        for raw in ["false", "False", " NO", "n", "OFF", "0", "\tf"] {
+            assert!(!parse_bool(raw).unwrap(), "{raw:?}");
+        }
+        for raw in ["", "2", "yess", "enabled", "o n"] {
+            assert!(parse_bool(raw).is_err(), "{raw:?}");
+        }
+    }
+
+    #[test]
+    fn parse_bool_with_uses_only_the_given_sets() {
+        let parse = |raw| parse_bool_with(raw, &["enabled", "ja"], &["disabled", "nein"]);
+        assert!(parse(" Enabled ").unwrap());
+        assert!(parse("JA").unwrap());
+        assert!(!parse("nein").unwrap());
+        assert!(parse("true").is_err());
+        assert_eq!(
+            parse("1").unwrap_err().to_string(),
+            "expected one of enabled, ja for true or disabled, nein for false (case-insensitive)"
+        );
+    }
+
+    #[test]
+    fn parse_bool_error_lists_the_accepted_values() {
+        assert_eq!(
+            parse_bool("maybe").unwrap_err().to_string(),
+            "expected one of true, t, yes, y, on, 1 for true \
+             or false, f, no, n, off, 0 for false (case-insensitive)"
+        );
+    }
+
+    fn duration(s: &str, unit: Option<&str>) -> Result<std::time::Duration, String> {
+        parse_duration(s, unit).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parse_duration_accepts_units_and_compound_values() {
+        use std::time::Duration;
+        assert_eq!(duration("30s", None), Ok(Duration::from_secs(30)));
+        assert_eq!(duration("1m30s", None), Ok(Duration::from_secs(90)));
+        assert_eq!(duration("1h 30m", None), Ok(Duration::from_secs(5400)));
+        assert_eq!(duration("250ms", None), Ok(Duration::from_millis(250)));
+        assert_eq!(duration("2d", None), Ok(Duration::from_secs(172_800)));
+        assert_eq!(duration(" 7ns ", None), Ok(Duration::from_nanos(7)));
+        assert_eq!(duration("15us", None), Ok(Duration::from_micros(15)));
+    }
+
+    #[test]
+    fn parse_duration_keeps_fractions_exact() {
+        use std::time::Duration;
+        assert_eq!(duration("1.5h", None), Ok(Duration::from_secs(5400)));
+        assert_eq!(duration("0.1s", None), Ok(Duration::from_millis(100)));
+        assert_eq!(duration(".5ms", None), Ok(Duration::from_micros(500)));
+        assert_eq!(
+            duration("1.000000000000000000000000000000000000000000s", None),
+            Ok(Duration::from_secs(1))
+        );
+        assert!(
+            duration("0.1ns", None)
+                .unwrap_err()
+                .contains("not a whole number of nanoseconds")
+        );
+        assert!(
+            duration("1.0000000001s", None)
+                .unwrap_err()
+                .contains("not a whole number")
+        );
+        assert!(
+            duration("1.00000000000000000000000000000000000000000000001s", None)
+                .unwrap_err()
+                .contains("not a whole number")
+        );
+    }
+
+    #[test]
+    fn parse_duration_reads_bare_numbers_in_unit() {
+        use std::time::Duration;
+        assert_eq!(duration("30", Some("ms")), Ok(Duration::from_millis(30)));
+        assert_eq!(duration("1.5", Some("s")), Ok(Duration::from_millis(1500)));
+        assert_eq!(duration("0", None), Ok(Duration::ZERO));
+        assert_eq!(duration(" 00 ", None), Ok(Duration::ZERO));
+        assert!(
+            duration("0.5", None)
+                .unwrap_err()
+                .starts_with("missing unit after `0.5`")
+        );
+        assert!(
+            duration("30", None)
+                .unwrap_err()
+                .starts_with("missing unit after `30`")
+        );
+    }
+
+    #[test]
+    fn parse_duration_rejects_malformed_values() {
+        assert!(
+            duration("", None)
+                .unwrap_err()
+                .starts_with("empty duration")
+        );
+        assert!(
+            duration("10y", None)
+                .unwrap_err()
+                .starts_with("unknown unit `y`")
+        );
+        assert!(
+            duration("s", None)
+                .unwrap_err()
+                .starts_with("expected a number at `s`")
+        );
+        assert!(
+            duration("1.2.3s", None)
+                .unwrap_err()
+                .starts_with("invalid number `1.2.3`")
+        );
+        assert!(
+            duration(".s", None)
+                .unwrap_err()
+                .starts_with("invalid number `.`")
+        );
+        assert!(duration("-1s", None).is_err());
+        assert!(
+            duration("1m30", None)
+                .unwrap_err()
+                .starts_with("missing unit after `30`")
+        );
+    }
+
+    #[test]
+    fn parse_duration_detects_overflow() {
+        let too_large = "duration is too large";
+        assert!(
+            duration("18446744073709551616s", None)
+                .unwrap_err()
+                .starts_with(too_large)
+        );
+        assert!(
+            duration("99999999999999999999999999999999999999999d", None)
+                .unwrap_err()
+                .starts_with(too_large)
+        );
+        assert!(
+            duration("18446744073709551615s 1s", None)
+                .unwrap_err()
+                .starts_with(too_large)
+        );
+    }
+
+    fn bytes<T: TryFrom<u128>>(s: &str) -> Result<T, String> {
+        parse_bytes::<T>(s).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parse_bytes_distinguishes_si_and_iec_units() {
+        assert_eq!(bytes::<u64>("512"), Ok(512));
+        assert_eq!(bytes::<u64>("512B"), Ok(512));
+        assert_eq!(bytes::<u64>("512k"), Ok(512_000));
+        assert_eq!(bytes::<u64>("512kB"), Ok(512_000));
+        assert_eq!(bytes::<u64>("512KiB"), Ok(524_288));
+        assert_eq!(bytes::<u64>("10MiB"), Ok(10 * 1024 * 1024));
+        assert_eq!(bytes::<u64>("10 mi"), Ok(10 * 1024 * 1024));
+        assert_eq!(bytes::<u64>("2GB"), Ok(2_000_000_000));
+        assert_eq!(bytes::<u64>("1TiB"), Ok(1 << 40));
+        assert_eq!(bytes::<u64>("1EiB"), Ok(1 << 60));
+    }
+
+    #[test]
+    fn parse_bytes_accepts_exact_fractions() {
+        assert_eq!(bytes::<u64>("1.5GB"), Ok(1_500_000_000));
+        assert_eq!(bytes::<u64>("1.5KiB"), Ok(1536));
+        assert_eq!(bytes::<u64>(".5k"), Ok(500));
+        assert_eq!(bytes::<u64>("2."), Ok(2));
+        // Trailing zeros do not make the fraction finer
+        assert_eq!(
+            bytes::<u64>("1.0000000000000000000000000000000000000000k"),
+            Ok(1000)
+        );
+        // 2^-39 EiB is 2 MiB, though 10^39 does not fit in a u128
+        assert_eq!(
+            bytes::<u64>("0.000000000001818989403545856475830078125EiB"),
+            Ok(2 * 1024 * 1024)
+        );
+        assert!(
+            bytes::<u64>("0.000000000001818989403545856475830078126EiB")
+                .unwrap_err()
+                .contains("not a whole number")
+        );
+        assert!(
+            bytes::<u64>("0.0000000000000000000000000000000000000000001k")
+                .unwrap_err()
+                .contains("not a whole number")
+        );
+        assert!(
+            bytes::<u64>("0.5B")
+                .unwrap_err()
+                .starts_with("`0.5B` is not a whole number of bytes")
+        );
+        assert!(
+            bytes::<u64>("1.0001k")
+                .unwrap_err()
+                .contains("not a whole number")
+        );
+    }
+
+    #[test]
+    fn parse_bytes_checks_the_target_type() {
+        assert_eq!(bytes::<u8>("255"), Ok(255));
+        assert!(
+            bytes::<u8>("1k")
+                .unwrap_err()
+                .starts_with("`1k` is too large for u8")
+        );
+        assert!(
+            bytes::<u64>("20EiB")
+                .unwrap_err()
+                .starts_with("`20EiB` is too large for u64")
+        );
+        assert!(
+            bytes::<i16>("32KiB")
+                .unwrap_err()
+                .starts_with("`32KiB` is too large for i16")
+        );
+        assert_eq!(bytes::<u128>("20EiB"), Ok(20 << 60));
+    }
+
+    #[test]
+    fn parse_bytes_rejects_malformed_values() {
+        assert!(
+            bytes::<u64>("")
+                .unwrap_err()
+                .starts_with("invalid byte size ``")
+        );
+        assert!(
+            bytes::<u64>("k")
+                .unwrap_err()
+                .starts_with("invalid byte size `k`")
+        );
+        assert!(
+            bytes::<u64>("-1k")
+                .unwrap_err()
+                .starts_with("invalid byte size")
+        );
+        assert!(
+            bytes::<u64>("1.2.3k")
+                .unwrap_err()
+                .starts_with("invalid number `1.2.3`")
+        );
+        assert!(
+            bytes::<u64>("10XB")
+                .unwrap_err()
+                .starts_with("unknown unit in `10XB`")
+        );
+        assert!(
+            bytes::<u64>("10i")
+                .unwrap_err()
+                .starts_with("unknown unit in `10i`")
+        );
+    }
+
+    fn int<T: EnvInt>(s: &str) -> Result<T, String> {
+        parse_int::<T>(s).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn parse_int_accepts_radix_prefixes_and_separators() {
+        assert_eq!(int::<u32>("42"), Ok(42));
+        assert_eq!(int::<u32>("0o644"), Ok(0o644));
+        assert_eq!(int::<u16>("0xFF00"), Ok(0xFF00));
+        assert_eq!(int::<u16>("0XffFF"), Ok(0xFFFF));
+        assert_eq!(int::<u8>("0b1010"), Ok(0b1010));
+        assert_eq!(int::<u64>("1_000_000"), Ok(1_000_000));
+        assert_eq!(int::<u32>("0xFF_FF"), Ok(0xFFFF));
+        assert_eq!(int::<i32>("-0x10"), Ok(-16));
+        assert_eq!(int::<i32>("+7"), Ok(7));
+        assert_eq!(int::<u32>(" 8 "), Ok(8));
+    }
+
+    #[test]
+    fn parse_int_reads_leading_zeros_as_decimal() {
+        assert_eq!(int::<u32>("0644"), Ok(644));
+        assert_eq!(int::<u32>("0"), Ok(0));
+    }
+
+    #[test]
+    fn parse_int_checks_the_range_of_the_type() {
+        assert_eq!(int::<i8>("-128"), Ok(-128));
+        assert_eq!(int::<u8>("0xFF"), Ok(255));
+        assert!(
+            int::<u8>("0x100")
+                .unwrap_err()
+                .starts_with("`0x100` is too large for u8")
+        );
+        assert!(
+            int::<i8>("-129")
+                .unwrap_err()
+                .starts_with("`-129` is too small for i8")
+        );
+        assert!(
+            int::<u8>("-1")
+                .unwrap_err()
+                .starts_with("`-1` is too small for u8")
+        );
+        assert!(
+            int::<u16>("-0x1_0000")
+                .unwrap_err()
+                .starts_with("`-0x1_0000` is too small for u16")
+        );
+        assert_eq!(int::<u32>("-0"), Ok(0));
+        assert!(
+            int::<u64>("-x")
+                .unwrap_err()
+                .starts_with("invalid integer `-x`")
+        );
+    }
+
+    #[test]
+    fn parse_int_shows_negative_examples_for_signed_types_only() {
+        assert_eq!(
+            int::<i32>("x").unwrap_err(),
+            "invalid integer `x`; expected an integer such as `42`, `-7`, `1_000_000`, \
+             `0xFF00`, `0o644` or `0b1010`"
+        );
+        assert_eq!(
+            int::<u32>("x").unwrap_err(),
+            "invalid integer `x`; expected an integer such as `42`, `1_000_000`, \
+             `0xFF00`, `0o644` or `0b1010`"
+        );
+    }
+
+    #[test]
+    fn parse_int_rejects_malformed_values() {
+        for raw in [
+            "", "abc", "0x", "0x_FF", "_1", "1_", "0x-5", "--1", "1.0", "0b102",
+        ] {
+            let err = int::<i32>(raw).unwrap_err();
+            assert!(err.starts_with("invalid integer"), "{raw}: {err}");
+        }
+    }
+
+    #[test]
+    fn parse_map_with_uses_the_given_parsers() {
+        let map: std::collections::BTreeMap<u8, bool> = parse_map_with(
+            "K",
+            "0x1=yes, 2=off".into(),
+            ",",
+            "=",
+            parse_int,
+            parse_bool,
+        )
+        .unwrap();
+        assert_eq!(map, [(1, true), (2, false)].into());
+
+        let err = parse_map_with::<u8, bool, std::collections::BTreeMap<_, _>, _, _, _, _>(
+            "K",
+            "1=yes,0x1=no".into(),
+            ",",
+            "=",
+            parse_int,
+            parse_bool,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("`0x1=no`"), "{err}");
+    }
+
+    /// The offending value and the parser's message of a `ParseError`
+    fn parse_error(err: CfgError) -> (String, String) {
+        match err {
+            CfgError::ParseError { value, source, .. } => (value, source.to_string()),
+            err => panic!("expected a parse error, got {err:?}"),
+        }
+    }
+
+    #[test]
+    fn parse_map_parses_keys_and_values_with_from_str() {
+        let map: std::collections::HashMap<String, u16> =
+            parse_map("K", " web = 80 ,, api=8080 ".into(), ",", "=").unwrap();
+        assert_eq!(map, [("web".into(), 80), ("api".into(), 8080)].into());
+
+        // Only the first separator splits an entry
+        let map: std::collections::BTreeMap<String, String> =
+            parse_map("K", "a:b:c;d:".into(), ";", ":").unwrap();
+        assert_eq!(
+            map,
+            [("a".into(), "b:c".into()), ("d".into(), "".into())].into()
+        );
+
+        // FromStr is strict, unlike parse_int
+        let err = parse_map::<String, u16, std::collections::HashMap<_, _>>(
+            "K",
+            "web=0x50".into(),
+            ",",
+            "=",
+        )
+        .unwrap_err();
+        assert_eq!(parse_error(err).0, "web=0x50");
+    }
+
+    #[test]
+    fn parse_map_rejects_malformed_entries_and_duplicate_keys() {
+        let err = parse_map::<String, u16, std::collections::HashMap<_, _>>(
+            "K",
+            "web=80,api".into(),
+            ",",
+            "=",
+        )
+        .unwrap_err();
+        assert_eq!(
+            parse_error(err),
+            (
+                "api".into(),
+                "expected an entry of the form `key=value`".into()
+            )
+        );
+
+        let err = parse_map::<String, u16, std::collections::BTreeMap<_, _>>(
+            "K",
+            "web=80, web = 81".into(),
+            ",",
+            "=",
+        )
+        .unwrap_err();
+        assert_eq!(
+            parse_error(err),
+            ("web = 81".into(), "duplicate key `web`".into())
+        );
+    }
+
+    #[test]
+    fn parse_set_rejects_duplicates_unless_dedup() {
+        use std::collections::{BTreeSet, HashSet};
+        let set: BTreeSet<u16> = parse_set("K", "443, 80,,8080".into(), ",", false).unwrap();
+        assert_eq!(set, [80, 443, 8080].into());
+
+        let err = parse_set::<u16, HashSet<_>>("K", "80,443,80".into(), ",", false).unwrap_err();
+        assert_eq!(
+            parse_error(err),
+            ("80,443,80".into(), "duplicate value `80`".into())
+        );
+
+        // Duplicates are found after parsing, so `0x50` repeats `80`
+        let err =
+            parse_set_with::<u16, BTreeSet<_>, _, _>("K", "80, 0x50".into(), ",", false, parse_int)
+                .unwrap_err();
+        assert_eq!(parse_error(err).1, "duplicate value `0x50`");
+
+        let set: BTreeSet<u16> =
+            parse_set_with("K", "80, 0x50, 443".into(), ",", true, parse_int).unwrap();
+        assert_eq!(set, [80, 443].into());
+    }
+
+    #[test]
+    fn parse_array_requires_exactly_n_values() {
+        let rgb: [u8; 3] = parse_array("K", "255, 128,0".into(), ",").unwrap();
+        assert_eq!(rgb, [255, 128, 0]);
+
+        let err = parse_array::<u8, 3>("K", "255,128".into(), ",").unwrap_err();
+        assert_eq!(
+            parse_error(err),
+            (
+                "255,128".into(),
+                "expected exactly 3 values, found 2".into()
+            )
+        );
+
+        let err = parse_array_with::<bool, _, _, 2>("K", "on,off,on".into(), ",", parse_bool)
+            .unwrap_err();
+        assert_eq!(parse_error(err).1, "expected exactly 2 values, found 3");
+    }
+
+    /// The key, raw value and rule of a `ValidationError`
+    fn validation(result: Result<(), CfgError>) -> Option<(String, String, String)> {
+        match result {
+            Ok(()) => None,
+            Err(CfgError::ValidationError { key, value, rule }) => Some((key, value, rule)),
+            Err(err) => panic!("expected a validation error, got {err:?}"),
+        }
+    }
+
+    #[test]
+    fn check_range_accepts_any_range_bounds() {
+        let check = |value: u16, range| validation(check_range("K", "raw", &value, range, "r"));
+        assert_eq!(check(1024, 1024..=65535), None);
+        assert_eq!(check(65535, 1024..=65535), None);
+        assert!(check(1023, 1024..=65535).is_some());
+        assert!(validation(check_range("K", "10", &10, 1..10, "r")).is_some());
+        assert_eq!(
+            validation(check_range("K", "0.5", &0.5, 0.0..1.0, "r")),
+            None
+        );
+        assert_eq!(validation(check_range("K", "9", &9, ..10, "r")), None);
+        assert_eq!(
+            validation(check_range("PORT", "0x3FF", &1023, 1024.., "range(1024..)")),
+            Some(("PORT".into(), "0x3FF".into(), "range(1024..)".into()))
+        );
+    }
+
+    #[test]
+    fn check_len_counts_characters_and_elements() {
+        let check = |value: &str, min, max| validation(check_len("K", value, value, min, max, "r"));
+        // Four characters, but eight bytes
+        assert_eq!(check("ñañá", Some(4), Some(4)), None);
+        assert!(check("ñañá", Some(5), None).is_some());
+        assert!(check("ñañá", None, Some(3)).is_some());
+        assert_eq!(check("", None, Some(0)), None);
+
+        let hosts = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            validation(check_len("K", "a,b", &hosts, Some(1), Some(2), "r")),
+            None
+        );
+        assert_eq!(
+            validation(check_len(
+                "HOSTS",
+                "a,b",
+                &hosts,
+                Some(3),
+                None,
+                "len(min = 3)"
+            )),
+            Some(("HOSTS".into(), "a,b".into(), "len(min = 3)".into()))
+        );
+    }
+
+    #[test]
+    fn check_one_of_compares_with_each_option() {
+        let levels = ["debug", "info"];
+        let check =
+            |value: &str| validation(check_one_of("K", value, &value.to_string(), &levels, "r"));
+        assert_eq!(check("info"), None);
+        // Comparison is exact
+        assert!(check("INFO").is_some());
+        assert_eq!(
+            validation(check_one_of("K", "0x2", &2u8, &[1, 2, 4], "r")),
+            None
+        );
+        assert_eq!(
+            validation(check_one_of(
+                "K",
+                "3",
+                &3u8,
+                &[1, 2, 4],
+                "one_of = [1, 2, 4]"
+            )),
+            Some(("K".into(), "3".into(), "one_of = [1, 2, 4]".into()))
+        );
+    }
+
+    /// The rule of a `ConstraintError`
+    fn constraint(result: Result<(), CfgError>) -> Option<String> {
+        match result {
+            Ok(()) => None,
+            Err(CfgError::ConstraintError { key, rule }) => Some(format!("{key} {rule}")),
+            Err(err) => panic!("expected a constraint error, got {err:?}"),
+        }
+    }
+
+    #[test]
+    fn check_requires_and_conflicts_treat_blank_as_unset() {
+        set_vars(&[("CONSTRAINT_A_KEY", "x"), ("CONSTRAINT_A_BLANK", " ")]);
+        let keys = |keys: &[&str]| -> Vec<String> {
+            keys.iter().map(|k| format!("CONSTRAINT_A_{k}")).collect()
+        };
+        assert_eq!(constraint(check_requires("CERT", &keys(&["KEY"]))), None);
+        assert_eq!(
+            constraint(check_requires("CERT", &keys(&["KEY", "BLANK", "UNSET"]))),
+            Some("CERT requires CONSTRAINT_A_BLANK to be set".into())
+        );
+        assert_eq!(
+            constraint(check_conflicts("CERT", &keys(&["BLANK", "UNSET"]))),
+            None
+        );
+        assert_eq!(
+            constraint(check_conflicts("CERT", &keys(&["UNSET", "KEY"]))),
+            Some("CERT conflicts with CONSTRAINT_A_KEY".into())
+        );
+    }
+
+    #[test]
+    fn check_required_if_ignores_case_and_whitespace() {
+        set_vars(&[("CONSTRAINT_B_ENV", " Production ")]);
+        let check = |value| constraint(check_required_if("URL", "CONSTRAINT_B_ENV", value));
+        assert_eq!(
+            check("production"),
+            Some("URL is required when CONSTRAINT_B_ENV is `production`".into())
+        );
+        assert_eq!(check("staging"), None);
+        let unset = check_required_if("URL", "CONSTRAINT_B_UNSET", "production");
+        assert_eq!(constraint(unset), None);
+    }
+
+    #[test]
+    fn run_validate_wraps_the_message() {
+        let err =
+            run_validate(&3u8, |n| if *n > 2 { Err("too many") } else { Ok(()) }).unwrap_err();
+        assert!(matches!(
+            err,
+            CfgError::StructValidationError { ty: "u8", .. }
+        ));
+        assert_eq!(err.to_string(), "invalid u8");
+        assert_eq!(
+            std::error::Error::source(&err).unwrap().to_string(),
+            "too many"
+        );
+    }
+
+    #[test]
+    fn escape_references_escapes_unquoted_and_double_quoted_references() {
+        assert_eq!(escape_references("A=${B}"), r"A=\${B}");
+        assert_eq!(escape_references(r#"A="x ${B}""#), r#"A="x \${B}""#);
+        assert_eq!(escape_references("A=$${B}"), r"A=\$\${B}");
+        // Bare `$VAR` is left to dotenvy
+        assert_eq!(escape_references("A=$B {C}"), "A=$B {C}");
+    }
+
+    #[test]
+    fn escape_references_keeps_single_quoted_references_literal() {
+        assert_eq!(escape_references("A='${B}'"), "A='$${B}'");
+        assert_eq!(escape_references(r#"A='"${B}'"#), r#"A='"$${B}'"#);
+        assert_eq!(escape_references(r#"A="it's ${B}""#), r#"A="it's \${B}""#);
+    }
+
+    #[test]
+    fn escape_references_respects_backslash_escapes() {
+        assert_eq!(escape_references(r"A=\'${B}"), r"A=\'\${B}");
+        assert_eq!(escape_references(r#"A="\"${B}""#), r#"A="\"\${B}""#);
+    }
+
+    #[test]
+    fn escape_references_skips_comments() {
+        let content = "# it's ${A}\nB=${C} # don't ${D}\nE=${F}";
+        assert_eq!(
+            escape_references(content),
+            "# it's ${A}\nB=\\${C} # don't ${D}\nE=\\${F}"
+        );
+        // `#` only starts a comment after whitespace
+        assert_eq!(escape_references("A=x#${B}"), r"A=x#\${B}");
+    }
+
+    #[test]
+    fn escape_references_tracks_quotes_across_lines() {
+        let content = "A=\"one\n${B}\"\nC='two\n${D}'\nE=${F}";
+        assert_eq!(
+            escape_references(content),
+            "A=\"one\n\\${B}\"\nC='two\n$${D}'\nE=\\${F}"
+        );
+    }
+
+    #[test]
+    fn interpolate_expands_against_the_environment() {
+        set_vars(&[
+            ("INTERP_A_USER", "app"),
+            ("INTERP_A_EMPTY", ""),
+            ("INTERP_A_RAW", "${x}"),
+        ]);
+        assert_eq!(interpolate("K", "no references").unwrap(), "no references");
+        assert_eq!(interpolate("K", "u=${INTERP_A_USER}!").unwrap(), "u=app!");
+        assert_eq!(interpolate("K", "${INTERP_A_EMPTY:-d}").unwrap(), "d");
+        assert_eq!(interpolate("K", "${INTERP_A_EMPTY-d}").unwrap(), "");
+        assert_eq!(interpolate("K", "${INTERP_A_UNSET-d}").unwrap(), "d");
+        assert_eq!(
+            interpolate("K", "${INTERP_A_UNSET:-${INTERP_A_USER}}").unwrap(),
+            "app"
+        );
+        assert_eq!(
+            interpolate("K", "$${INTERP_A_USER} $5").unwrap(),
+            "${INTERP_A_USER} $5"
+        );
+        // Environment values are used as is
+        assert_eq!(interpolate("K", "${INTERP_A_RAW}").unwrap(), "${x}");
+    }
+
+    #[test]
+    fn interpolate_reports_undefined_and_malformed_references() {
+        let err = |raw: &str| interpolate("K", raw).unwrap_err().to_string();
+        assert_eq!(
+            err("${INTERP_B_UNSET}"),
+            "failed to interpolate env K: undefined variable INTERP_B_UNSET"
+        );
+        assert_eq!(
+            err("${INTERP_B_UNSET:?}"),
+            "failed to interpolate env K: INTERP_B_UNSET is not set"
+        );
+        assert_eq!(
+            err("${INTERP_B_UNSET:?set it}"),
+            "failed to interpolate env K: INTERP_B_UNSET: set it"
+        );
+        assert_eq!(
+            err("${}"),
+            "failed to interpolate env K: invalid reference `${}`"
+        );
+        assert_eq!(
+            err("${A:x}"),
+            "failed to interpolate env K: invalid reference `${A:x}`"
+        );
+        assert_eq!(
+            err("${A"),
+            "failed to interpolate env K: unterminated `${` in `${A`"
+        );
+    }
+
+    /// Write `content` to a .env file unique to the test and load it
+    fn load_test_env(name: &str, content: &str) -> Result<(), CfgError> {
+        let path = env::temp_dir().join(format!("cfgloader-{}-{name}.env", std::process::id()));
+        std::fs::write(&path, content).unwrap();
+        let loaded = load_env_file(&path);
+        std::fs::remove_file(&path).unwrap();
+        loaded
+    }
+
+    #[test]
+    fn load_env_file_expands_values_before_setting_them() {
+        set_vars(&[("ENVFILE_A_PASS", "p${w}")]);
+        load_test_env(
+            "expand",
+            "ENVFILE_A_URL=db://${ENVFILE_A_USER}:${ENVFILE_A_PASS}@${ENVFILE_A_HOST:-localhost}\n\
+             ENVFILE_A_USER=app\n\
+             ENVFILE_A_LITERAL='${ENVFILE_A_USER}'\n\
+             ENVFILE_A_ESCAPED=a$${ENVFILE_A_USER}\n\
+             ENVFILE_A_QUOTED=\"${ENVFILE_A_LITERAL}\"\n\
+             ENVFILE_A_USER=ignored\n",
+        )
+        .unwrap();
+        let var = |key| env::var(key).unwrap();
+        // Referenced .env values are expanded, environment values used as is
+        assert_eq!(var("ENVFILE_A_URL"), "db://app:p${w}@localhost");
+        assert_eq!(var("ENVFILE_A_USER"), "app");
+        assert_eq!(var("ENVFILE_A_LITERAL"), "${ENVFILE_A_USER}");
+        assert_eq!(var("ENVFILE_A_ESCAPED"), "a${ENVFILE_A_USER}");
+        assert_eq!(var("ENVFILE_A_QUOTED"), "${ENVFILE_A_USER}");
+    }
+
+    #[test]
+    fn load_env_file_keeps_existing_variables() {
+        set_vars(&[("ENVFILE_B_SET", "env")]);
+        load_test_env(
+            "existing",
+            "ENVFILE_B_SET=${ENVFILE_B_UNSET}\nENVFILE_B_NEW=${ENVFILE_B_SET}\n",
+        )
+        .unwrap();
+        assert_eq!(env::var("ENVFILE_B_SET").unwrap(), "env");
+        assert_eq!(env::var("ENVFILE_B_NEW").unwrap(), "env");
+    }
+
+    #[test]
+    fn load_env_file_sets_nothing_when_a_value_fails() {
+        let err = load_test_env(
+            "cycle",
+            "ENVFILE_C_OK=1\nENVFILE_C_A=${ENVFILE_C_B}\nENVFILE_C_B=x${ENVFILE_C_A}\n",
+        )
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "failed to interpolate env ENVFILE_C_A: cyclic reference ENVFILE_C_A -> ENVFILE_C_B -> ENVFILE_C_A"
+        );
+        assert!(env::var("ENVFILE_C_OK").is_err());
+
+        let err = load_test_env("undefined", "ENVFILE_D_A=${ENVFILE_D_UNSET}\n").unwrap_err();
+        assert!(matches!(err, CfgError::InterpolationError { .. }));
+    }
+}
@@ -20,7 +20,7 @@ struct Config {
     /// If not set, defaults to "sqlite://test.db"
     #[env("DB_URL", default = "sqlite://test.db")]
     db_url: String,
-    /// Nested configuration struct - will automatically call App::load()
+    /// Nested configuration struct - loaded through its own FromEnv implementation
     /// Fields without #[env] annotations are treated as nested configurations
     app: App,
 }
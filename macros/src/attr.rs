@@ -0,0 +1,497 @@
+//! Parsing of `#[env(...)]` attributes.
+//!
+//! The grammar for a field attribute is:
+//!
+//! ```text
+//! #[env("KEY", alias = "OTHER_KEY", deprecated = "OLD_KEY",
+//!       default = "value", required, split = ",", kv_split = "=", dedup,
+//!       with = path::to_fn, unit = "s", bytes, strict)]
+//! ```
+//!
+//! A field may also be constrained against other keys with
+//! `requires = "KEY"`, `conflicts_with = "KEY"` and `required_if(KEY = "value")`.
+//!
+//! Parsed values may be validated with `range(1..=10)`, `len(min = 1, max = 64)`,
+//! `regex = "^[a-z]+$"` and `one_of = ["a", "b"]`.
+//!
+//! `default` takes a string literal (parsed like the variable itself), any
+//! other expression (used as is), or no value (`Default::default()`).
+//! `default_expr` always takes an expression.
+//!
+//! The key must come first and may be omitted, in which case it is derived
+//! from the field name (see `rename_all`). `alias`, `deprecated`, `requires`,
+//! `conflicts_with` and `required_if` may be repeated; every other option may
+//! appear at most once across all `#[env]` attributes of a field.
+//!
+//! Nested fields take `#[env(prefix = "...", enabled_by = "...")]` instead of
+//! a key, `Vec`s of nested structs take `#[env(indexed = "...")]`, and maps of
+//! nested structs take `#[env(keyed = "...")]`. The struct itself accepts
+//! `#[env(prefix = "...", rename_all = "...", validate = path::to_fn)]`. On a
+//! newtype struct, a key and value options may be given on the struct instead
+//! of its single field.
+//!
+//! Variants of an enum deriving `EnvValue` accept
+//! `#[env(rename = "...", alias = "...")]`.
+
+use syn::ext::IdentExt;
+use syn::parse::ParseStream;
+use syn::{Attribute, Ident, LitStr, Meta, Token};
+
+use crate::case::RenameRule;
+
+/// Options collected from the `#[env(...)]` attributes of a single field.
+#[derive(Default)]
+pub(crate) struct FieldAttrs {
+    /// The first `#[env]` attribute, used for errors about the field as a whole
+    pub attr: Option<Attribute>,
+    pub key: Option<LitStr>,
+    pub default: Option<DefaultValue>,
+    pub required: Option<Ident>,
+    pub split: Option<LitStr>,
+    pub kv_split: Option<LitStr>,
+    /// On set fields, drop duplicate values instead of rejecting them
+    pub dedup: Option<Ident>,
+    pub prefix: Option<LitStr>,
+    /// Load a `Vec` of nested structs from `{indexed}0_`, `{indexed}1_`, ...
+    pub indexed: Option<LitStr>,
+    /// Load a map of nested structs from every `{keyed}{NAME}_` group
+    pub keyed: Option<LitStr>,
+    /// On an `Option<T>` nested field, the key of the toggle enabling it
+    pub enabled_by: Option<LitStr>,
+    pub with: Option<syn::Path>,
+    /// The unit of bare numbers on `Duration` fields
+    pub unit: Option<LitStr>,
+    /// Parse integers as byte sizes such as `10MiB`
+    pub bytes: Option<Ident>,
+    /// Parse `bool` and integer fields with `FromStr` alone
+    pub strict: Option<Ident>,
+    /// Fallback keys checked in order after the key
+    pub aliases: Vec<LitStr>,
+    /// Old keys checked after the aliases, reported as deprecated when used
+    pub deprecated: Vec<LitStr>,
+    pub range: Option<syn::ExprRange>,
+    pub len: Option<LenRule>,
+    pub regex: Option<LitStr>,
+    pub one_of: Option<syn::ExprArray>,
+    /// Keys that must be set when this field's key is
+    pub requires: Vec<LitStr>,
+    /// Keys that must not be set when this field's key is
+    pub conflicts_with: Vec<LitStr>,
+    /// `(KEY, value)` conditions under which the field must be set
+    pub required_if: Vec<(LitStr, LitStr)>,
+}
+
+impl FieldAttrs {
+    /// Whether any option that only makes sense for a keyed value is present
+    pub(crate) fn has_value_options(&self) -> bool {
+        self.key.is_some()
+            || self.default.is_some()
+            || self.required.is_some()
+            || self.split.is_some()
+            || self.kv_split.is_some()
+            || self.dedup.is_some()
+            || self.with.is_some()
+            || self.unit.is_some()
+            || self.bytes.is_some()
+            || self.strict.is_some()
+            || !self.aliases.is_empty()
+            || !self.deprecated.is_empty()
+            || self.range.is_some()
+            || self.len.is_some()
+            || self.regex.is_some()
+            || self.one_of.is_some()
+            || !self.requires.is_empty()
+            || !self.conflicts_with.is_empty()
+            || !self.required_if.is_empty()
+    }
+
+    /// Parse every `#[env]` attribute in `attrs`, ignoring all other attributes
+    pub(crate) fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = FieldAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("env")) {
+            out.attr.get_or_insert_with(|| attr.clone());
+            // A bare #[env] derives the key from the field name
+            if let Meta::Path(_) = attr.meta {
+                continue;
+            }
+            attr.parse_args_with(|input: ParseStream| {
+                parse_args(input, |arg, input| out.parse_arg(arg, input))
+            })?;
+        }
+        Ok(out)
+    }
+
+    fn set_default(&mut self, value: DefaultValue, name: &Ident) -> syn::Result<()> {
+        if self.default.is_some() {
+            return Err(syn::Error::new(
+                name.span(),
+                "duplicate default, use only one of `default` and `default_expr`",
+            ));
+        }
+        self.default = Some(value);
+        Ok(())
+    }
+
+    fn parse_arg(&mut self, arg: Arg, input: ParseStream) -> syn::Result<()> {
+        let name = match arg {
+            Arg::Key(key) => {
+                if self.key.is_some() {
+                    return Err(syn::Error::new(key.span(), "duplicate env key"));
+                }
+                self.key = Some(key);
+                return Ok(());
+            }
+            Arg::Option(name) => name,
+        };
+        match name.to_string().as_str() {
+            "default" => {
+                let value = if input.peek(Token![=]) {
+                    input.parse::<Token![=]>()?;
+                    match input.parse()? {
+                        syn::Expr::Lit(syn::ExprLit {
+                            lit: syn::Lit::Str(lit),
+                            ..
+                        }) => DefaultValue::Str(lit),
+                        expr => DefaultValue::Expr(expr),
+                    }
+                } else {
+                    DefaultValue::Trait(name.clone())
+                };
+                self.set_default(value, &name)
+            }
+            "default_expr" => {
+                input.parse::<Token![=]>()?;
+                self.set_default(DefaultValue::Expr(input.parse()?), &name)
+            }
+            "required" => set_once(&mut self.required, name.clone(), &name),
+            "dedup" => set_once(&mut self.dedup, name.clone(), &name),
+            "bytes" => set_once(&mut self.bytes, name.clone(), &name),
+            "strict" => set_once(&mut self.strict, name.clone(), &name),
+            "split" | "kv_split" => {
+                let value = parse_str_value(input)?;
+                if value.value().is_empty() {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        format!("`{name}` separator must not be empty"),
+                    ));
+                }
+                if name == "split" {
+                    set_once(&mut self.split, value, &name)
+                } else {
+                    set_once(&mut self.kv_split, value, &name)
+                }
+            }
+            "prefix" => set_once(&mut self.prefix, parse_str_value(input)?, &name),
+            "indexed" => set_once(&mut self.indexed, parse_str_value(input)?, &name),
+            "keyed" => set_once(&mut self.keyed, parse_str_value(input)?, &name),
+            "enabled_by" => set_once(&mut self.enabled_by, parse_str_value(input)?, &name),
+            "with" => {
+                input.parse::<Token![=]>()?;
+                set_once(&mut self.with, input.parse()?, &name)
+            }
+            "range" => {
+                let content;
+                syn::parenthesized!(content in input);
+                set_once(&mut self.range, content.parse()?, &name)
+            }
+            "len" => {
+                let content;
+                syn::parenthesized!(content in input);
+                let len = LenRule::parse(name.clone(), &content)?;
+                set_once(&mut self.len, len, &name)
+            }
+            "regex" => set_once(&mut self.regex, parse_str_value(input)?, &name),
+            "unit" => set_once(&mut self.unit, parse_str_value(input)?, &name),
+            "one_of" => {
+                input.parse::<Token![=]>()?;
+                let options: syn::ExprArray = input.parse()?;
+                if options.elems.is_empty() {
+                    return Err(syn::Error::new_spanned(
+                        options,
+                        "`one_of` needs at least one value",
+                    ));
+                }
+                set_once(&mut self.one_of, options, &name)
+            }
+            "requires" | "conflicts_with" => {
+                let key = parse_str_value(input)?;
+                if name == "requires" {
+                    self.requires.push(key);
+                } else {
+                    self.conflicts_with.push(key);
+                }
+                Ok(())
+            }
+            "required_if" => {
+                let content;
+                syn::parenthesized!(content in input);
+                // The key may be written bare, `KEY = "..."`, or quoted
+                let key = if content.peek(LitStr) {
+                    content.parse()?
+                } else {
+                    let key = content.call(Ident::parse_any)?;
+                    LitStr::new(&key.to_string(), key.span())
+                };
+                let value = parse_str_value(&content)?;
+                self.required_if.push((key, value));
+                Ok(())
+            }
+            "alias" | "deprecated" => {
+                let key = parse_str_value(input)?;
+                if key.value().is_empty() {
+                    return Err(syn::Error::new(key.span(), "env key must not be empty"));
+                }
+                if name == "alias" {
+                    self.aliases.push(key);
+                } else {
+                    self.deprecated.push(key);
+                }
+                Ok(())
+            }
+            _ => Err(unknown_option(
+                &name,
+                &[
+                    "default",
+                    "default_expr",
+                    "required",
+                    "split",
+                    "kv_split",
+                    "dedup",
+                    "prefix",
+                    "indexed",
+                    "keyed",
+                    "enabled_by",
+                    "with",
+                    "unit",
+                    "bytes",
+                    "strict",
+                    "alias",
+                    "deprecated",
+                    "range",
+                    "len",
+                    "regex",
+                    "one_of",
+                    "requires",
+                    "conflicts_with",
+                    "required_if",
+                ],
+            )),
+        }
+    }
+}
+
+/// `len(min = .., max = ..)`, with at least one bound
+pub(crate) struct LenRule {
+    pub name: Ident,
+    pub min: Option<syn::Expr>,
+    pub max: Option<syn::Expr>,
+}
+
+impl LenRule {
+    fn parse(name: Ident, input: ParseStream) -> syn::Result<Self> {
+        let mut rule = LenRule {
+            name,
+            min: None,
+            max: None,
+        };
+        while !input.is_empty() {
+            let bound: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match bound.to_string().as_str() {
+                "min" => set_once(&mut rule.min, input.parse()?, &bound)?,
+                "max" => set_once(&mut rule.max, input.parse()?, &bound)?,
+                _ => return Err(unknown_option(&bound, &["min", "max"])),
+            }
+            if input.is_empty() {
+                break;
+            }
+            input.parse::<Token![,]>()?;
+        }
+        if rule.min.is_none() && rule.max.is_none() {
+            return Err(syn::Error::new(
+                rule.name.span(),
+                "`len` needs a `min` or `max` bound",
+            ));
+        }
+        Ok(rule)
+    }
+}
+
+/// The value used when a field's variable is missing
+pub(crate) enum DefaultValue {
+    /// `default = "..."`, parsed at runtime like the variable itself
+    Str(LitStr),
+    /// `default = <expr>` or `default_expr = <expr>`, used as is
+    Expr(syn::Expr),
+    /// Bare `default`, the type's `Default` implementation
+    Trait(Ident),
+}
+
+/// Options collected from the `#[env(...)]` attributes on the struct itself.
+#[derive(Default)]
+pub(crate) struct ContainerAttrs {
+    pub prefix: Option<LitStr>,
+    pub rename_all: Option<RenameRule>,
+    /// A `fn(&Self) -> Result<(), E>` run once every field has loaded
+    pub validate: Option<syn::Path>,
+    /// Key and value options given on a newtype struct for its single field
+    pub field: FieldAttrs,
+}
+
+impl ContainerAttrs {
+    /// Parse every `#[env]` attribute in `attrs`, ignoring all other attributes
+    pub(crate) fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = ContainerAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("env")) {
+            attr.parse_args_with(|input: ParseStream| {
+                parse_args(input, |arg, input| out.parse_arg(arg, attr, input))
+            })?;
+        }
+        Ok(out)
+    }
+
+    fn parse_arg(&mut self, arg: Arg, attr: &Attribute, input: ParseStream) -> syn::Result<()> {
+        let name = match arg {
+            Arg::Option(name) if name == "prefix" || name == "rename_all" || name == "validate" => {
+                name
+            }
+            // Anything else configures the single field of a newtype
+            arg => {
+                self.field.attr.get_or_insert_with(|| attr.clone());
+                return self.field.parse_arg(arg, input);
+            }
+        };
+        match name.to_string().as_str() {
+            "prefix" => set_once(&mut self.prefix, parse_str_value(input)?, &name),
+            "rename_all" => {
+                let value = parse_str_value(input)?;
+                let Some(rule) = RenameRule::from_name(&value.value()) else {
+                    return Err(syn::Error::new(
+                        value.span(),
+                        format!(
+                            "unknown rename_all rule, expected one of: {}",
+                            RenameRule::NAMES.join(", ")
+                        ),
+                    ));
+                };
+                set_once(&mut self.rename_all, rule, &name)
+            }
+            "validate" => {
+                input.parse::<Token![=]>()?;
+                set_once(&mut self.validate, input.parse()?, &name)
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+/// Options collected from the `#[env(...)]` attributes of an enum variant
+/// deriving `EnvValue`.
+#[derive(Default)]
+pub(crate) struct VariantAttrs {
+    pub rename: Option<LitStr>,
+    pub aliases: Vec<LitStr>,
+}
+
+impl VariantAttrs {
+    /// Parse every `#[env]` attribute in `attrs`, ignoring all other attributes
+    pub(crate) fn from_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
+        let mut out = VariantAttrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("env")) {
+            attr.parse_args_with(|input: ParseStream| {
+                parse_args(input, |arg, input| out.parse_arg(arg, input))
+            })?;
+        }
+        Ok(out)
+    }
+
+    fn parse_arg(&mut self, arg: Arg, input: ParseStream) -> syn::Result<()> {
+        let name = match arg {
+            Arg::Key(key) => {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "an env key is not supported on enum variants, use `rename = \"...\"`",
+                ));
+            }
+            Arg::Option(name) => name,
+        };
+        match name.to_string().as_str() {
+            "rename" => set_once(&mut self.rename, parse_str_value(input)?, &name),
+            "alias" => {
+                self.aliases.push(parse_str_value(input)?);
+                Ok(())
+            }
+            _ => Err(unknown_option(&name, &["rename", "alias"])),
+        }
+    }
+}
+
+/// A single top-level argument of `#[env(...)]`
+enum Arg {
+    /// The leading `"KEY"` literal
+    Key(LitStr),
+    /// A named option; its value, if any, is still in the input stream
+    Option(Ident),
+}
+
+/// Walk the comma separated arguments of `#[env(...)]`, handing each one to `f`
+fn parse_args(
+    input: ParseStream,
+    mut f: impl FnMut(Arg, ParseStream) -> syn::Result<()>,
+) -> syn::Result<()> {
+    let mut first = true;
+    while !input.is_empty() {
+        if input.peek(LitStr) {
+            let key: LitStr = input.parse()?;
+            if !first {
+                return Err(syn::Error::new(
+                    key.span(),
+                    "the env key must be the first argument of #[env(...)]",
+                ));
+            }
+            if key.value().is_empty() {
+                return Err(syn::Error::new(key.span(), "env key must not be empty"));
+            }
+            f(Arg::Key(key), input)?;
+        } else {
+            let name = input.call(Ident::parse_any)?;
+            f(Arg::Option(name), input)?;
+        }
+        first = false;
+        if input.is_empty() {
+            break;
+        }
+        input.parse::<Token![,]>()?;
+    }
+    Ok(())
+}
+
+/// Parse `= "literal"` following an option name
+fn parse_str_value(input: ParseStream) -> syn::Result<LitStr> {
+    input.parse::<Token![=]>()?;
+    input.parse()
+}
+
+/// Store `value` in `slot`, rejecting a second occurrence of the option `name`
+fn set_once<T>(slot: &mut Option<T>, value: T, name: &Ident) -> syn::Result<()> {
+    if slot.is_some() {
+        return Err(syn::Error::new(
+            name.span(),
+            format!("duplicate env option `{name}`"),
+        ));
+    }
+    *slot = Some(value);
+    Ok(())
+}
+
+fn unknown_option(name: &Ident, expected: &[&str]) -> syn::Error {
+    let expected = expected
+        .iter()
+        .map(|o| format!("`{o}`"))
+        .collect::<Vec<_>>()
+        .join(", ");
+    syn::Error::new(
+        name.span(),
+        format!("unknown env option `{name}`, expected one of: {expected}"),
+    )
+}
@@ -0,0 +1,67 @@
+//! Where-clause predicates for generic structs deriving `FromEnv`.
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::{Generics, Ident, WherePredicate};
+
+/// Collects the bounds each field's loading code needs from its type, keeping
+/// only those whose type mentions one of the struct's type parameters.
+pub(crate) struct Bounds {
+    params: Vec<Ident>,
+    predicates: Vec<WherePredicate>,
+}
+
+impl Bounds {
+    pub(crate) fn new(generics: &Generics) -> Self {
+        Bounds {
+            params: generics.type_params().map(|p| p.ident.clone()).collect(),
+            predicates: Vec::new(),
+        }
+    }
+
+    /// Require `ty: bound`
+    pub(crate) fn add(&mut self, ty: &syn::Type, bound: TokenStream) {
+        if self.mentions_param(ty) {
+            self.push(syn::parse_quote! { #ty: #bound });
+        }
+    }
+
+    /// Require what `parse_scalar::<ty>` and `parse_vec::<ty>` need
+    pub(crate) fn add_from_str(&mut self, ty: &syn::Type) {
+        if self.mentions_param(ty) {
+            self.push(syn::parse_quote! { #ty: ::std::str::FromStr });
+            self.push(syn::parse_quote! {
+                <#ty as ::std::str::FromStr>::Err:
+                    ::std::error::Error + ::std::marker::Send + ::std::marker::Sync + 'static
+            });
+        }
+    }
+
+    /// Add the collected predicates to the where clause of `generics`
+    pub(crate) fn apply(self, generics: &mut Generics) {
+        if !self.predicates.is_empty() {
+            generics
+                .make_where_clause()
+                .predicates
+                .extend(self.predicates);
+        }
+    }
+
+    fn push(&mut self, predicate: WherePredicate) {
+        if !self.predicates.contains(&predicate) {
+            self.predicates.push(predicate);
+        }
+    }
+
+    fn mentions_param(&self, ty: &syn::Type) -> bool {
+        !self.params.is_empty() && mentions(ty.to_token_stream(), &self.params)
+    }
+}
+
+fn mentions(tokens: TokenStream, params: &[Ident]) -> bool {
+    tokens.into_iter().any(|tt| match tt {
+        TokenTree::Ident(ident) => params.contains(&ident),
+        TokenTree::Group(group) => mentions(group.stream(), params),
+        _ => false,
+    })
+}
@@ -0,0 +1,193 @@
+//! Compile-time checks for string defaults of built-in types.
+//!
+//! A default such as `default = "80800"` on a `u16` would otherwise only fail
+//! at runtime, and only where the variable is unset. For primitives, `bool`,
+//! `char` and `Vec` of those the literal is parsed during expansion with the
+//! same functions the generated code uses at runtime. The elements of other
+//! collections of those types, `Duration`s and byte sizes are checked the same
+//! way, as are the number of values for an array with a literal length and
+//! duplicate values for a set without `dedup`.
+
+use syn::LitStr;
+
+use crate::attr::FieldAttrs;
+use crate::ty::{ValueKind, is_duration};
+
+/// Parse `default` as `ty` the way the field's options parse its value,
+/// erroring on the literal if it is invalid
+pub(crate) fn check_default(
+    ty: &syn::Type,
+    default: &LitStr,
+    attrs: &FieldAttrs,
+) -> syn::Result<()> {
+    let split = attrs
+        .split
+        .as_ref()
+        .map_or_else(|| ",".to_string(), |s| s.value());
+    let split = split.as_str();
+    let unit = attrs.unit.as_ref().map(|unit| unit.value());
+    let unit = unit.as_deref();
+    // Collections are checked element by element
+    let kind = ValueKind::of(ty);
+    let (item, split) = match kind.item() {
+        Some(item) => (item, Some(split)),
+        None => (ty, None),
+    };
+    let shape = match kind {
+        ValueKind::Array(_, len) => array_len(len).map_or(Shape::Any, Shape::Exactly),
+        ValueKind::Set(_) if attrs.dedup.is_none() => Shape::Distinct,
+        _ => Shape::Any,
+    };
+    let raw = default.value();
+    let parsed = if is_duration(item) {
+        let parser = |raw: &str| cfgloader_core::parse_duration(raw, unit);
+        let parsed = match split {
+            Some(sep) => cfgloader_core::parse_vec_with("", raw.clone(), sep, parser)
+                .map(|items| shape.check(&raw, sep, &items)),
+            None => cfgloader_core::parse_with("", raw.clone(), parser).map(|_| Ok(())),
+        };
+        Some(("Duration".to_string(), parsed))
+    } else {
+        builtin_name(item).and_then(|name| {
+            let parser = match (&attrs.bytes, &attrs.strict) {
+                (Some(_), _) => Parser::Bytes,
+                (None, None) => Parser::Lenient,
+                (None, Some(_)) => Parser::FromStr,
+            };
+            let parsed = parse_builtin(&name, &raw, split, shape, parser)?;
+            Some((name, parsed))
+        })
+    };
+    let msg = match parsed {
+        Some((name, Err(cfgloader_core::CfgError::ParseError { value, source, .. }))) => {
+            format!("invalid default `{value}` for {name}: {source}")
+        }
+        Some((_, Ok(Err(msg)))) => format!("invalid default `{raw}`: {msg}"),
+        _ => return Ok(()),
+    };
+    Err(syn::Error::new(default.span(), msg))
+}
+
+/// The length of an array type written as an integer literal, e.g. `[u8; 3]`
+fn array_len(len: &syn::Expr) -> Option<usize> {
+    match len {
+        syn::Expr::Lit(syn::ExprLit {
+            lit: syn::Lit::Int(int),
+            ..
+        }) => int.base10_parse().ok(),
+        _ => None,
+    }
+}
+
+/// What the parsed elements of a collection default must satisfy
+#[derive(Clone, Copy)]
+enum Shape {
+    Any,
+    /// A set without `dedup`
+    Distinct,
+    /// An array of this length
+    Exactly(usize),
+}
+
+impl Shape {
+    /// Check `items` parsed from `raw`, with the same messages as at runtime
+    fn check<T: PartialEq>(self, raw: &str, sep: &str, items: &[T]) -> Result<(), String> {
+        match self {
+            Shape::Any => Ok(()),
+            Shape::Distinct => {
+                let Some(i) = (1..items.len()).find(|&i| items[..i].contains(&items[i])) else {
+                    return Ok(());
+                };
+                let part = raw
+                    .split(sep)
+                    .map(str::trim)
+                    .filter(|s| !s.is_empty())
+                    .nth(i)
+                    .unwrap_or_default();
+                Err(format!("duplicate value `{part}`"))
+            }
+            Shape::Exactly(len) if items.len() != len => Err(format!(
+                "expected exactly {len} values, found {}",
+                items.len()
+            )),
+            Shape::Exactly(_) => Ok(()),
+        }
+    }
+}
+
+/// The name of a built-in type written as a bare identifier, e.g. `u16`
+fn builtin_name(ty: &syn::Type) -> Option<String> {
+    match ty {
+        syn::Type::Path(tp) if tp.qself.is_none() => {
+            tp.path.get_ident().map(|ident| ident.to_string())
+        }
+        _ => None,
+    }
+}
+
+/// Which runtime parser the field's options select
+enum Parser {
+    FromStr,
+    /// [`cfgloader_core::parse_bool`] for `bool`, [`cfgloader_core::parse_int`]
+    /// for integers and `FromStr` otherwise
+    Lenient,
+    Bytes,
+}
+
+/// Run the runtime parser for the built-in type `name`, or None if it isn't one.
+///
+/// A parse error is returned as is; the elements of a collection are then
+/// checked against `shape`.
+fn parse_builtin(
+    name: &str,
+    raw: &str,
+    split: Option<&str>,
+    shape: Shape,
+    parser: Parser,
+) -> Option<Result<Result<(), String>, cfgloader_core::CfgError>> {
+    macro_rules! dispatch_with {
+        ($parser:expr; $($ty:ident)*) => {
+            match name {
+                $(stringify!($ty) => Some(match split {
+                    Some(sep) => cfgloader_core::parse_vec_with::<$ty, _, _>(
+                        "", raw.to_string(), sep, $parser,
+                    )
+                    .map(|items| shape.check(raw, sep, &items)),
+                    None => cfgloader_core::parse_with::<$ty, _, _>("", raw.to_string(), $parser)
+                        .map(|_| Ok(())),
+                }),)*
+                _ => None,
+            }
+        };
+    }
+    macro_rules! dispatch {
+        ($($ty:ident)*) => {
+            match name {
+                $(stringify!($ty) => Some(match split {
+                    Some(sep) => cfgloader_core::parse_vec::<$ty>("", raw.to_string(), sep)
+                        .map(|items| shape.check(raw, sep, &items)),
+                    None => cfgloader_core::parse_scalar::<$ty>("", raw.to_string())
+                        .map(|_| Ok(())),
+                }),)*
+                _ => None,
+            }
+        };
+    }
+    match parser {
+        Parser::Bytes => dispatch_with!(
+            cfgloader_core::parse_bytes;
+            i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize
+        ),
+        Parser::Lenient => match name {
+            "bool" => dispatch_with!(cfgloader_core::parse_bool; bool),
+            "char" | "f32" | "f64" => dispatch!(char f32 f64),
+            _ => dispatch_with!(
+                cfgloader_core::parse_int;
+                i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize
+            ),
+        },
+        Parser::FromStr => {
+            dispatch!(bool char i8 i16 i32 i64 i128 isize u8 u16 u32 u64 u128 usize f32 f64)
+        }
+    }
+}
@@ -0,0 +1,164 @@
+//! Deriving env keys from field names for `#[env(rename_all = "...")]`.
+
+/// A casing convention applied to field names that have no explicit key
+#[derive(Clone, Copy, Default)]
+pub(crate) enum RenameRule {
+    #[default]
+    ScreamingSnake,
+    Snake,
+    Lower,
+    Upper,
+    Kebab,
+    ScreamingKebab,
+    Camel,
+    Pascal,
+}
+
+impl RenameRule {
+    pub(crate) const NAMES: &'static [&'static str] = &[
+        "SCREAMING_SNAKE_CASE",
+        "snake_case",
+        "lowercase",
+        "UPPERCASE",
+        "kebab-case",
+        "SCREAMING-KEBAB-CASE",
+        "camelCase",
+        "PascalCase",
+    ];
+
+    pub(crate) fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "SCREAMING_SNAKE_CASE" => RenameRule::ScreamingSnake,
+            "snake_case" => RenameRule::Snake,
+            "lowercase" => RenameRule::Lower,
+            "UPPERCASE" => RenameRule::Upper,
+            "kebab-case" => RenameRule::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameRule::ScreamingKebab,
+            "camelCase" => RenameRule::Camel,
+            "PascalCase" => RenameRule::Pascal,
+            _ => return None,
+        })
+    }
+
+    /// Apply the rule to a Rust field name such as `max_connections`
+    pub(crate) fn apply(self, field: &str) -> String {
+        let field = field.strip_prefix("r#").unwrap_or(field);
+        let words = words(field);
+        match self {
+            RenameRule::ScreamingSnake => join(&words, "_", str::to_uppercase),
+            RenameRule::Snake => join(&words, "_", str::to_lowercase),
+            RenameRule::Lower => join(&words, "", str::to_lowercase),
+            RenameRule::Upper => join(&words, "", str::to_uppercase),
+            RenameRule::Kebab => join(&words, "-", str::to_lowercase),
+            RenameRule::ScreamingKebab => join(&words, "-", str::to_uppercase),
+            RenameRule::Camel => {
+                let mut out = String::new();
+                for (i, word) in words.iter().enumerate() {
+                    if i == 0 {
+                        out.push_str(&word.to_lowercase());
+                    } else {
+                        out.push_str(&capitalize(word));
+                    }
+                }
+                out
+            }
+            RenameRule::Pascal => words.iter().map(|w| capitalize(w)).collect(),
+        }
+    }
+}
+
+/// Split a name into words on underscores and lower-to-upper case boundaries
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut prev_lower = false;
+    for c in name.chars() {
+        if c == '_' {
+            if !current.is_empty() {
+                words.push(std::mem::take(&mut current));
+            }
+            prev_lower = false;
+            continue;
+        }
+        if c.is_uppercase() && prev_lower && !current.is_empty() {
+            words.push(std::mem::take(&mut current));
+        }
+        prev_lower = c.is_lowercase() || c.is_ascii_digit();
+        current.push(c);
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+fn join(words: &[String], sep: &str, case: fn(&str) -> String) -> String {
+    words.iter().map(|w| case(w)).collect::<Vec<_>>().join(sep)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first
+            .to_uppercase()
+            .chain(chars.flat_map(char::to_lowercase))
+            .collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RenameRule;
+
+    #[test]
+    fn applies_every_rule() {
+        let fields = ["max_connections", "r#type", "http2_port", "port"];
+        let table: &[(&str, [&str; 4])] = &[
+            (
+                "SCREAMING_SNAKE_CASE",
+                ["MAX_CONNECTIONS", "TYPE", "HTTP2_PORT", "PORT"],
+            ),
+            (
+                "snake_case",
+                ["max_connections", "type", "http2_port", "port"],
+            ),
+            ("lowercase", ["maxconnections", "type", "http2port", "port"]),
+            ("UPPERCASE", ["MAXCONNECTIONS", "TYPE", "HTTP2PORT", "PORT"]),
+            (
+                "kebab-case",
+                ["max-connections", "type", "http2-port", "port"],
+            ),
+            (
+                "SCREAMING-KEBAB-CASE",
+                ["MAX-CONNECTIONS", "TYPE", "HTTP2-PORT", "PORT"],
+            ),
+            ("camelCase", ["maxConnections", "type", "http2Port", "port"]),
+            (
+                "PascalCase",
+                ["MaxConnections", "Type", "Http2Port", "Port"],
+            ),
+        ];
+        assert_eq!(table.len(), RenameRule::NAMES.len());
+        for (name, expected) in table {
+            let rule = RenameRule::from_name(name).unwrap();
+            for (field, want) in fields.iter().zip(expected) {
+                assert_eq!(rule.apply(field), *want, "{name} on {field}");
+            }
+        }
+    }
+
+    #[test]
+    fn from_name_covers_names() {
+        for name in RenameRule::NAMES {
+            assert!(RenameRule::from_name(name).is_some(), "{name}");
+        }
+        assert!(RenameRule::from_name("Pascal").is_none());
+        assert!(RenameRule::from_name("snake").is_none());
+    }
+
+    #[test]
+    fn default_is_screaming_snake() {
+        assert_eq!(RenameRule::default().apply("log_level"), "LOG_LEVEL");
+    }
+}
@@ -0,0 +1,77 @@
+//! `#[derive(EnvValue)]`: `FromStr` for unit-only enums.
+
+use quote::quote;
+use syn::{Data, DeriveInput, Fields};
+
+use crate::attr::VariantAttrs;
+
+pub(crate) fn expand_env_value(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    if let Some(attr) = input.attrs.iter().find(|a| a.path().is_ident("env")) {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "#[env(...)] is only supported on the variants of an EnvValue enum",
+        ));
+    }
+    let Data::Enum(data) = &input.data else {
+        return Err(syn::Error::new_spanned(
+            name,
+            "EnvValue only supports enums",
+        ));
+    };
+
+    let mut arms = Vec::new();
+    // Lowercased spellings seen so far, to reject ambiguous renames and aliases
+    let mut seen: Vec<String> = Vec::new();
+    let mut expected = Vec::new();
+    for variant in &data.variants {
+        if !matches!(variant.fields, Fields::Unit) {
+            return Err(syn::Error::new_spanned(
+                variant,
+                "EnvValue only supports unit variants",
+            ));
+        }
+        let attrs = VariantAttrs::from_attrs(&variant.attrs)?;
+        let ident = &variant.ident;
+        let primary = match attrs.rename {
+            Some(rename) => rename,
+            None => syn::LitStr::new(&ident.to_string(), ident.span()),
+        };
+
+        for spelling in std::iter::once(&primary).chain(&attrs.aliases) {
+            let value = spelling.value();
+            if value.is_empty() {
+                return Err(syn::Error::new(spelling.span(), "value must not be empty"));
+            }
+            let lower = value.to_ascii_lowercase();
+            if seen.contains(&lower) {
+                return Err(syn::Error::new(
+                    spelling.span(),
+                    format!("`{value}` is already accepted by another variant"),
+                ));
+            }
+            seen.push(lower);
+            expected.push(value.clone());
+            arms.push(quote! {
+                if s.eq_ignore_ascii_case(#spelling) {
+                    return Ok(Self::#ident);
+                }
+            });
+        }
+    }
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::std::str::FromStr for #name #ty_generics #where_clause {
+            type Err = ::cfgloader_rs::ParseEnumError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                #(#arms)*
+                Err(::cfgloader_rs::ParseEnumError {
+                    value: s.to_string(),
+                    expected: &[#(#expected),*],
+                })
+            }
+        }
+    })
+}
@@ -1,189 +1,206 @@
 //! Procedural macros for cfgloader
 //!
 //! This crate provides the `FromEnv` derive macro that automatically generates
-//! configuration loading code from environment variables.
+//! configuration loading code from environment variables, and the `EnvValue`
+//! derive macro that parses unit-only enums from their variant names.
+
+/// `quote!` with mixed-site hygiene: the locals of the generated code, such as
+/// `prefix`, `keys` and `value`, are not visible to expressions from attributes
+macro_rules! quote_mixed {
+    ($($tt:tt)*) => {
+        ::quote::quote_spanned!(::proc_macro2::Span::mixed_site()=> $($tt)*)
+    };
+}
+
+mod attr;
+mod bounds;
+mod builtin;
+mod case;
+mod env_value;
+mod ty;
+mod validate;
 
 // Proc macro implementation
+use attr::{ContainerAttrs, DefaultValue, FieldAttrs};
+use bounds::Bounds;
+use case::RenameRule;
 use proc_macro::TokenStream;
-use quote::quote;
+use quote::quote_spanned;
+use syn::ext::IdentExt;
+use syn::spanned::Spanned;
 use syn::{Data, DeriveInput, Fields, parse_macro_input};
+use ty::{ValueKind, generic_arg, is_bool, is_duration, is_integer};
 
 #[proc_macro_derive(FromEnv, attributes(env))]
 pub fn derive_from_env(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
+    expand_from_env(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[proc_macro_derive(EnvValue, attributes(env))]
+pub fn derive_env_value(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    env_value::expand_env_value(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand_from_env(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let name = input.ident;
+    let container = ContainerAttrs::from_attrs(&input.attrs)?;
 
     let fields = match input.data {
         Data::Struct(s) => match s.fields {
             Fields::Named(f) => f.named,
-            Fields::Unnamed(_) | Fields::Unit => {
-                return syn::Error::new_spanned(
+            Fields::Unnamed(f) => f.unnamed,
+            Fields::Unit => {
+                return Err(syn::Error::new_spanned(
                     name,
-                    "FromEnv only supports structs with named fields",
-                )
-                .to_compile_error()
-                .into();
+                    "FromEnv does not support unit structs",
+                ));
             }
         },
         _ => {
-            return syn::Error::new_spanned(name, "FromEnv only supports structs")
-                .to_compile_error()
-                .into();
+            return Err(syn::Error::new_spanned(
+                name,
+                "FromEnv only supports structs",
+            ));
         }
     };
 
-    let mut inits = Vec::new();
-
-    for field in fields {
-        let ident = field.ident.unwrap();
-        let ty = field.ty.clone();
-
-        // Default: no env annotation → if type also implements FromEnv, call its load(); otherwise use Default (if implemented)
-        // If #[env(...)] is present, fill value according to rules
-        let mut key_tokens = None::<proc_macro2::TokenStream>;
-        let mut default_tokens = None::<proc_macro2::TokenStream>;
-        let mut required = false;
-        let mut split_tokens = None::<proc_macro2::TokenStream>;
-
-        for attr in field.attrs.iter().filter(|a| a.path().is_ident("env")) {
-            // Parse #[env("KEY", default = "value", required, split = ",")]
-            match &attr.meta {
-                syn::Meta::List(list) => {
-                    let tokens = &list.tokens;
-                    let content = tokens.to_string();
-
-                    // Parse something like "DB_URL", default = "sqlite://test.db"
-                    if let Some(stripped) = content.strip_prefix('"') {
-                        // Find the first string literal as the key
-                        if let Some(first_quote_end) = stripped.find('"') {
-                            let key = &stripped[..first_quote_end];
-                            key_tokens = Some(quote! { #key });
-
-                            // Look for additional arguments after the key
-                            let remaining = &content[first_quote_end + 2..];
-
-                            // Parse default = "..."
-                            if let Some(default_start) = remaining.find("default") {
-                                let default_part = &remaining[default_start..];
-                                if let Some(eq_pos) = default_part.find('=') {
-                                    let value_part = default_part[eq_pos + 1..].trim();
-                                    if value_part.starts_with('"')
-                                        && let Some(end_quote) = value_part[1..].find('"')
-                                    {
-                                        let value = &value_part[1..end_quote + 1];
-                                        default_tokens = Some(quote! { #value });
-                                    }
-                                }
-                            }
-
-                            // Check for required
-                            if remaining.contains("required") {
-                                required = true;
-                            }
-
-                            // Parse split = "..."
-                            if let Some(split_start) = remaining.find("split") {
-                                let split_part = &remaining[split_start..];
-                                if let Some(eq_pos) = split_part.find('=') {
-                                    let value_part = split_part[eq_pos + 1..].trim();
-                                    if value_part.starts_with('"')
-                                        && let Some(end_quote) = value_part[1..].find('"')
-                                    {
-                                        let value = &value_part[1..end_quote + 1];
-                                        split_tokens = Some(quote! { #value });
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                _ => {
-                    // Skip other attribute formats for now
-                }
-            }
+    // A key on the struct itself configures the single field of a newtype
+    if let Some(attr) = &container.field.attr {
+        let newtype = fields.len() == 1 && fields[0].ident.is_none();
+        if !newtype {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "a struct-level env key and value options are only supported on newtype structs",
+            ));
         }
+        if let Some(field_attr) = fields[0].attrs.iter().find(|a| a.path().is_ident("env")) {
+            return Err(syn::Error::new_spanned(
+                field_attr,
+                "#[env(...)] cannot be given on both a newtype struct and its field",
+            ));
+        }
+    }
 
-        let init_one = if let Some(key) = key_tokens {
-            let (ty_item_opt, _is_vec) = element_type(&ty);
-            if let Some(ty_item) = ty_item_opt {
-                // Vec<T>
-                let split = split_tokens.clone().unwrap_or_else(|| quote! { "," });
-                if required && default_tokens.is_none() {
-                    // For required Vec fields without default
-                    quote! {
-                        #ident: {
-                            match ::cfgloader_rs::get_env(#key) {
-                                Some(ref raw) if !raw.trim().is_empty() => ::cfgloader_rs::parse_vec::<#ty_item>(#key, raw.clone(), #split)?,
-                                _ => return Err(::cfgloader_rs::CfgError::MissingEnv(#key))
-                            }
-                        }
-                    }
-                } else {
-                    let default_branch = if let Some(def) = default_tokens.clone() {
-                        quote! {
-                            ::cfgloader_rs::parse_vec::<#ty_item>(#key, #def.to_string(), #split)?
-                        }
-                    } else {
-                        quote! { Vec::<#ty_item>::new() }
-                    };
-                    quote! {
-                        #ident: {
-                            match ::cfgloader_rs::get_env(#key) {
-                                Some(ref raw) if !raw.trim().is_empty() => ::cfgloader_rs::parse_vec::<#ty_item>(#key, raw.clone(), #split)?,
-                                _ => #default_branch
-                            }
-                        }
-                    }
-                }
-            } else {
-                // scalar
-                if required && default_tokens.is_none() {
-                    // For required fields without default, we need special handling
-                    quote! {
-                        #ident: {
-                            match ::cfgloader_rs::get_env(#key) {
-                                Some(ref raw) if !raw.trim().is_empty() => ::cfgloader_rs::parse_scalar::<#ty>(#key, raw.clone())?,
-                                _ => return Err(::cfgloader_rs::CfgError::MissingEnv(#key))
-                            }
-                        }
-                    }
-                } else {
-                    let default_branch = if let Some(def) = default_tokens.clone() {
-                        quote! { ::cfgloader_rs::parse_scalar::<#ty>(#key, #def.to_string())? }
-                    } else {
-                        quote! { Default::default() }
-                    };
-                    quote! {
-                        #ident: {
-                            match ::cfgloader_rs::get_env(#key) {
-                                Some(ref raw) if !raw.trim().is_empty() => ::cfgloader_rs::parse_scalar::<#ty>(#key, raw.clone())?,
-                                _ => #default_branch
-                            }
-                        }
-                    }
-                }
-            }
-        } else {
-            // No #[env]: directly call T::load() for nested loading
-            quote! {
-                #ident: {
-                    #ty::load(env_path)?
-                }
-            }
+    // Report problems in every field at once rather than one per compile
+    let rename_all = container.rename_all.unwrap_or_default();
+    let mut container_field = Some(container.field);
+    let mut bounds = Bounds::new(&input.generics);
+    let mut members = Vec::new();
+    let mut values = Vec::new();
+    let mut collects = Vec::new();
+    let mut field_keys = Vec::new();
+    let mut errors: Option<syn::Error> = None;
+    for (index, field) in fields.into_iter().enumerate() {
+        let attrs = match container_field.take().filter(|f| f.attr.is_some()) {
+            Some(attrs) => Ok(attrs),
+            None => FieldAttrs::from_attrs(&field.attrs),
         };
-
-        inits.push(init_one);
+        let member = match field.ident {
+            Some(ident) => syn::Member::Named(ident),
+            None => syn::Member::Unnamed(syn::Index {
+                index: index as u32,
+                span: field.ty.span(),
+            }),
+        };
+        match attrs.and_then(|attrs| field_init(&member, field.ty, attrs, rename_all, &mut bounds))
+        {
+            Ok(code) => {
+                members.push(member);
+                values.push(code.value);
+                collects.push(code.collect);
+                field_keys.push(code.keys);
+            }
+            Err(e) => match &mut errors {
+                Some(errors) => errors.combine(e),
+                None => errors = Some(e),
+            },
+        }
     }
+    if let Some(errors) = errors {
+        return Err(errors);
+    }
+
+    let mut generics = input.generics;
+    bounds.apply(&mut generics);
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
 
-    let expanded = quote! {
-        impl ::cfgloader_rs::FromEnv for #name {
+    // A struct-level prefix goes between the caller's prefix and every key
+    let struct_prefix = container.prefix.as_ref().map(|p| {
+        quote_mixed! { let prefix = &format!("{}{}", prefix, #p); }
+    });
+    let own_prefix = container
+        .prefix
+        .map_or_else(|| quote_mixed! { "" }, |p| quote_mixed! { #p });
+    let key_count = field_keys.len();
+    let validate = container.validate.map(|validate| {
+        quote_mixed! { ::cfgloader_rs::run_validate(&cfg, #validate)?; }
+    });
+    let vars: Vec<_> = (0..members.len())
+        .map(|i| quote::format_ident!("field_{}", i, span = proc_macro2::Span::mixed_site()))
+        .collect();
+
+    Ok(quote_mixed! {
+        impl #impl_generics ::cfgloader_rs::FromEnv for #name #ty_generics #where_clause {
             fn load(env_path: &std::path::Path) -> Result<Self, ::cfgloader_rs::CfgError> {
                 // Load .env file if it exists
                 ::cfgloader_rs::load_env_file(env_path)?;
 
-                Ok(Self {
-                    #(#inits),*
-                })
+                Self::load_prefixed("")
+            }
+
+            fn load_prefixed_with_report(
+                prefix: &str,
+                report: &mut ::cfgloader_rs::LoadReport,
+            ) -> Result<Self, ::cfgloader_rs::CfgError> {
+                #struct_prefix
+
+                let cfg = Self {
+                    #(#members: #values),*
+                };
+                #validate
+                Ok(cfg)
+            }
+
+            fn load_fields(
+                prefix: &str,
+                report: &mut ::cfgloader_rs::LoadReport,
+            ) -> Result<Self, Vec<::cfgloader_rs::FieldError>> {
+                #struct_prefix
+
+                // Try every field, keeping going past failures
+                let mut errors = Vec::new();
+                #(
+                    let #vars = match #collects {
+                        Ok(value) => Some(value),
+                        Err(e) => {
+                            errors.extend(e);
+                            None
+                        }
+                    };
+                )*
+                if !errors.is_empty() {
+                    return Err(errors);
+                }
+                #(let Some(#vars) = #vars else { unreachable!() };)*
+                let cfg = Self {
+                    #(#members: #vars),*
+                };
+                #validate
+                Ok(cfg)
+            }
+
+            fn env_keys() -> Vec<String> {
+                let keys: [Vec<String>; #key_count] = [#(#field_keys),*];
+                keys.concat()
+                    .into_iter()
+                    .map(|key| format!("{}{}", #own_prefix, key))
+                    .collect()
             }
 
             fn load_iter<I, P>(paths: I) -> Result<Self, ::cfgloader_rs::CfgError>
@@ -207,20 +224,494 @@ pub fn derive_from_env(input: TokenStream) -> TokenStream {
                 }))
             }
         }
-    };
+    })
+}
 
-    expanded.into()
+/// The generated code for a single field
+struct FieldCode {
+    /// The field's value, returning the first error
+    value: proc_macro2::TokenStream,
+    /// A `Result<T, Vec<FieldError>>` of the field's value, collecting every error
+    collect: proc_macro2::TokenStream,
+    /// A `Vec<String>` of the keys the field reads, relative to the struct's prefix
+    keys: proc_macro2::TokenStream,
 }
 
-/// Return Some(T) if Vec<T>, otherwise None
-fn element_type(ty: &syn::Type) -> (Option<syn::Type>, bool) {
-    if let syn::Type::Path(tp) = ty
-        && let Some(seg) = tp.path.segments.last()
-        && seg.ident == "Vec"
-        && let syn::PathArguments::AngleBracketed(ab) = &seg.arguments
-        && let Some(syn::GenericArgument::Type(inner)) = ab.args.first()
+/// Generate the value and key list for a single field
+fn field_init(
+    member: &syn::Member,
+    ty: syn::Type,
+    attrs: FieldAttrs,
+    rename_all: RenameRule,
+    bounds: &mut Bounds,
+) -> syn::Result<FieldCode> {
+    // The field's name in the paths of collected errors
+    let path = match member {
+        syn::Member::Named(ident) => ident.unraw().to_string(),
+        syn::Member::Unnamed(index) => index.index.to_string(),
+    };
+    if attrs.indexed.is_some() && attrs.keyed.is_some() {
+        return Err(syn::Error::new_spanned(
+            &attrs.attr,
+            "`indexed` cannot be combined with `keyed`",
+        ));
+    }
+    // Vec of nested structs, one per index found under the prefix
+    if let Some(indexed) = &attrs.indexed {
+        if attrs.prefix.is_some() || attrs.enabled_by.is_some() || attrs.has_value_options() {
+            return Err(syn::Error::new_spanned(
+                &attrs.attr,
+                "`indexed` cannot be combined with other env options",
+            ));
+        }
+        let Some(item) = generic_arg(&ty, "Vec") else {
+            return Err(syn::Error::new_spanned(
+                &ty,
+                "`indexed` is only supported on Vec fields",
+            ));
+        };
+        bounds.add(item, quote_mixed! { ::cfgloader_rs::FromEnv });
+        let prefix = quote_mixed! { &format!("{}{}", prefix, #indexed) };
+        return Ok(FieldCode {
+            value: quote_mixed! { ::cfgloader_rs::load_indexed::<#item>(#prefix, report)? },
+            collect: quote_mixed! {
+                ::cfgloader_rs::load_section(#path, || {
+                    ::cfgloader_rs::load_indexed_fields::<#item>(#prefix, report)
+                })
+            },
+            keys: quote_mixed! { Vec::new() },
+        });
+    }
+
+    // Map of nested structs, one per `{keyed}{NAME}_` group found in the environment
+    if let Some(keyed) = &attrs.keyed {
+        if attrs.prefix.is_some() || attrs.enabled_by.is_some() || attrs.has_value_options() {
+            return Err(syn::Error::new_spanned(
+                &attrs.attr,
+                "`keyed` cannot be combined with other env options",
+            ));
+        }
+        let ValueKind::Map(k, v) = ValueKind::of(&ty) else {
+            return Err(syn::Error::new_spanned(
+                &ty,
+                "`keyed` is only supported on HashMap and BTreeMap fields",
+            ));
+        };
+        bounds.add_from_str(k);
+        bounds.add(v, quote_mixed! { ::cfgloader_rs::FromEnv });
+        bounds.add(&ty, quote_mixed! { ::cfgloader_rs::EnvMap<#k, #v> });
+        let prefix = quote_mixed! { &format!("{}{}", prefix, #keyed) };
+        return Ok(FieldCode {
+            value: quote_mixed! { ::cfgloader_rs::load_keyed::<#k, #v, #ty>(#prefix, report)? },
+            collect: quote_mixed! {
+                ::cfgloader_rs::load_section(#path, || {
+                    ::cfgloader_rs::load_keyed_fields::<#k, #v, #ty>(#prefix, report)
+                })
+            },
+            keys: quote_mixed! { Vec::new() },
+        });
+    }
+
+    // No #[env], or only a prefix and toggle: nested struct loaded under our prefix plus its own
+    let nested = attrs.attr.is_none()
+        || (!attrs.has_value_options() && (attrs.prefix.is_some() || attrs.enabled_by.is_some()));
+    if nested {
+        // An Option<T> section is only loaded when enabled
+        let optional = generic_arg(&ty, "Option");
+        if let (None, Some(toggle)) = (optional, &attrs.enabled_by) {
+            return Err(syn::Error::new(
+                toggle.span(),
+                "`enabled_by` is only supported on Option<T> nested fields",
+            ));
+        }
+        let section_ty = optional.unwrap_or(&ty);
+        let field_prefix = attrs
+            .prefix
+            .as_ref()
+            .map_or_else(|| quote_mixed! { "" }, |p| quote_mixed! { #p });
+        let prefix = match &attrs.prefix {
+            Some(p) => quote_mixed! { &format!("{}{}", prefix, #p) },
+            None => quote_mixed! { prefix },
+        };
+        bounds.add(section_ty, quote_mixed! { ::cfgloader_rs::FromEnv });
+        let section_keys = quote_mixed! {
+            <#section_ty as ::cfgloader_rs::FromEnv>::env_keys()
+                .into_iter()
+                .map(|key| format!("{}{}", #field_prefix, key))
+        };
+        // The same code loads the section failing fast or collecting errors
+        let section = |load: proc_macro2::TokenStream| match (optional, &attrs.enabled_by) {
+            (None, _) => quote_mixed! {{
+                let prefix: &str = #prefix;
+                #load
+            }},
+            (Some(_), Some(toggle)) => quote_mixed! {{
+                // The toggle sits next to the field, not inside the section
+                let enabled = ::cfgloader_rs::section_enabled(&format!("{}{}", prefix, #toggle))?;
+                let prefix: &str = #prefix;
+                if enabled { Some(#load) } else { None }
+            }},
+            (Some(_), None) => quote_mixed! {{
+                let prefix: &str = #prefix;
+                let keys = <#section_ty as ::cfgloader_rs::FromEnv>::env_keys();
+                if ::cfgloader_rs::section_present(prefix, &keys) { Some(#load) } else { None }
+            }},
+        };
+        let value = section(quote_mixed! {
+            <#section_ty as ::cfgloader_rs::FromEnv>::load_prefixed_with_report(prefix, report)?
+        });
+        let collect = section(quote_mixed! {
+            <#section_ty as ::cfgloader_rs::FromEnv>::load_fields(prefix, report)?
+        });
+        let keys = match &attrs.enabled_by {
+            Some(toggle) => quote_mixed! { #section_keys.chain([#toggle.to_string()]).collect() },
+            None => quote_mixed! { #section_keys.collect() },
+        };
+        return Ok(FieldCode {
+            value,
+            collect: quote_mixed! { ::cfgloader_rs::load_section(#path, || Ok(#collect)) },
+            keys,
+        });
+    }
+
+    let key = match (&attrs.key, member) {
+        (Some(key), _) => key.clone(),
+        (None, syn::Member::Named(ident)) => {
+            syn::LitStr::new(&rename_all.apply(&ident.to_string()), ident.span())
+        }
+        (None, syn::Member::Unnamed(_)) => {
+            return Err(syn::Error::new_spanned(
+                &attrs.attr,
+                "tuple struct fields need an explicit env key, e.g. #[env(\"KEY\")]",
+            ));
+        }
+    };
+    // Deprecated keys are tried last and reported when used
+    let keys = std::iter::once(&key)
+        .chain(&attrs.aliases)
+        .chain(&attrs.deprecated)
+        .collect::<Vec<_>>();
+    let current_keys = 1 + attrs.aliases.len();
+    let report_deprecated = (!attrs.deprecated.is_empty()).then(|| {
+        quote_mixed! {
+            if keys[#current_keys..].iter().any(|k| k == key) {
+                report.deprecations.push(::cfgloader_rs::Deprecation {
+                    key: key.to_string(),
+                    replacement: keys[0].clone(),
+                });
+            }
+        }
+    });
+    if let Some(prefix) = &attrs.prefix {
+        return Err(syn::Error::new(
+            prefix.span(),
+            "`prefix` is only supported on nested fields without an env key",
+        ));
+    }
+    if let Some(toggle) = &attrs.enabled_by {
+        return Err(syn::Error::new(
+            toggle.span(),
+            "`enabled_by` is only supported on nested fields without an env key",
+        ));
+    }
+    if let (Some(required), Some(_)) = (&attrs.required, attrs.required_if.first()) {
+        return Err(syn::Error::new(
+            required.span(),
+            "`required` cannot be combined with `required_if`",
+        ));
+    }
+    if let (Some(required), Some(_)) = (&attrs.required, &attrs.default) {
+        return Err(syn::Error::new(
+            required.span(),
+            "`required` cannot be combined with `default`",
+        ));
+    }
+    // Option<T> is None when the variable is absent, so it cannot be required
+    let optional = generic_arg(&ty, "Option");
+    if let (Some(_), Some(required)) = (optional, &attrs.required) {
+        return Err(syn::Error::new(
+            required.span(),
+            "`required` is not supported on Option<T> fields",
+        ));
+    }
+    let value_ty = optional.unwrap_or(&ty);
+
+    let split = attrs
+        .split
+        .as_ref()
+        .map(|s| quote_mixed! { #s })
+        .unwrap_or_else(|| quote_mixed! { "," });
+    let kind = ValueKind::of(value_ty);
+    if !matches!(kind, ValueKind::Map(..)) {
+        reject_option(
+            &attrs.kv_split,
+            "`kv_split` is only supported on map fields",
+        )?;
+        if kind.item().is_none() {
+            reject_option(
+                &attrs.split,
+                "`split` is only supported on collection and map fields",
+            )?;
+        }
+    }
+    if !matches!(kind, ValueKind::Set(_)) {
+        reject_option(&attrs.dedup, "`dedup` is only supported on set fields")?;
+    }
+    let dedup = attrs.dedup.is_some();
+    let checks = validate::checks(&attrs, &kind)?;
+    // Durations have no FromStr, so they get a built-in parser
+    let duration =
+        !matches!(kind, ValueKind::Map(..)) && is_duration(kind.item().unwrap_or(value_ty));
+    if let Some(unit) = &attrs.unit {
+        if !duration {
+            return Err(syn::Error::new(
+                unit.span(),
+                "`unit` is only supported on Duration fields",
+            ));
+        }
+        reject_option(&attrs.with, "`with` cannot be combined with `unit`")?;
+        if cfgloader_core::parse_duration("1", Some(&unit.value())).is_err() {
+            return Err(syn::Error::new(
+                unit.span(),
+                "unknown duration unit, expected one of: ns, us, ms, s, m, h, d",
+            ));
+        }
+    }
+    let item = kind.item().unwrap_or(value_ty);
+    if let Some(bytes) = &attrs.bytes {
+        if matches!(kind, ValueKind::Map(..)) || !is_integer(item) {
+            return Err(syn::Error::new(
+                bytes.span(),
+                "`bytes` is only supported on integer fields",
+            ));
+        }
+        reject_option(&attrs.with, "`with` cannot be combined with `bytes`")?;
+        reject_option(&attrs.strict, "`strict` cannot be combined with `bytes`")?;
+    }
+    // Booleans accept spellings such as `yes` and `off`, and integers radix
+    // prefixes and `_` separators, unless `strict`
+    let lenient_parser = |ty: &syn::Type| {
+        if is_bool(ty) {
+            Some(quote_mixed! { ::cfgloader_rs::parse_bool })
+        } else if is_integer(ty) {
+            Some(quote_mixed! { ::cfgloader_rs::parse_int })
+        } else {
+            None
+        }
+    };
+    let (lenient, lenient_map) = match &kind {
+        ValueKind::Map(k, v) => (None, (lenient_parser(k), lenient_parser(v))),
+        _ => (lenient_parser(item), (None, None)),
+    };
+    if let Some(strict) = &attrs.strict
+        && lenient.is_none()
+        && matches!(lenient_map, (None, None))
     {
-        return (Some(inner.clone()), true);
+        return Err(syn::Error::new(
+            strict.span(),
+            "`strict` is only supported on bool and integer fields",
+        ));
+    }
+    let with = match (&attrs.with, duration) {
+        (Some(with), _) => Some(quote_mixed! { #with }),
+        (None, _) if attrs.bytes.is_some() => Some(quote_mixed! { ::cfgloader_rs::parse_bytes }),
+        (None, _) if attrs.strict.is_none() && lenient.is_some() => lenient,
+        (None, true) => {
+            let unit = match &attrs.unit {
+                Some(unit) => quote_mixed! { Some(#unit) },
+                None => quote_mixed! { None },
+            };
+            Some(quote_mixed! { |raw: &str| ::cfgloader_rs::parse_duration(raw, #unit) })
+        }
+        (None, false) => None,
+    };
+    let mut parse = |raw: proc_macro2::TokenStream| match (&kind, &with) {
+        (ValueKind::Vec(item), _) => parse_vec(item, &raw, &split, &with, bounds),
+        (ValueKind::FromVec(item), _) => {
+            let vec = parse_vec(item, &raw, &split, &with, bounds);
+            quote_mixed! { <#value_ty as ::std::convert::From<Vec<#item>>>::from(#vec) }
+        }
+        (ValueKind::Set(item), Some(with)) => {
+            bounds.add(value_ty, quote_mixed! { ::cfgloader_rs::EnvSet<#item> });
+            quote_mixed! {
+                ::cfgloader_rs::parse_set_with::<#item, #value_ty, _, _>(key, #raw, #split, #dedup, #with)?
+            }
+        }
+        (ValueKind::Set(item), None) => {
+            bounds.add_from_str(item);
+            bounds.add(value_ty, quote_mixed! { ::cfgloader_rs::EnvSet<#item> });
+            quote_mixed! {
+                ::cfgloader_rs::parse_set::<#item, #value_ty>(key, #raw, #split, #dedup)?
+            }
+        }
+        (ValueKind::Array(item, len), Some(with)) => quote_mixed! {
+            ::cfgloader_rs::parse_array_with::<#item, _, _, { #len }>(key, #raw, #split, #with)?
+        },
+        (ValueKind::Array(item, len), None) => {
+            bounds.add_from_str(item);
+            quote_mixed! { ::cfgloader_rs::parse_array::<#item, { #len }>(key, #raw, #split)? }
+        }
+        (ValueKind::Map(k, v), None) => {
+            let kv_split = attrs
+                .kv_split
+                .as_ref()
+                .map(|s| quote_mixed! { #s })
+                .unwrap_or_else(|| quote_mixed! { "=" });
+            bounds.add(value_ty, quote_mixed! { ::cfgloader_rs::EnvMap<#k, #v> });
+            match (&lenient_map, attrs.strict.is_some()) {
+                ((None, None), _) | (_, true) => {
+                    bounds.add_from_str(k);
+                    bounds.add_from_str(v);
+                    quote_mixed! {
+                        ::cfgloader_rs::parse_map::<#k, #v, #value_ty>(key, #raw, #split, #kv_split)?
+                    }
+                }
+                ((parse_key, parse_value), false) => {
+                    let mut parser =
+                        |ty: &syn::Type, lenient: &Option<proc_macro2::TokenStream>| {
+                            lenient.clone().unwrap_or_else(|| {
+                                bounds.add_from_str(ty);
+                                quote_mixed! { <#ty as ::std::str::FromStr>::from_str }
+                            })
+                        };
+                    let (parse_key, parse_value) = (parser(k, parse_key), parser(v, parse_value));
+                    quote_mixed! {
+                        ::cfgloader_rs::parse_map_with::<#k, #v, #value_ty, _, _, _, _>(
+                            key, #raw, #split, #kv_split, #parse_key, #parse_value,
+                        )?
+                    }
+                }
+            }
+        }
+        // A custom parser on a map or scalar parses the whole value
+        (ValueKind::Map(..) | ValueKind::Scalar, Some(with)) => quote_mixed! {
+            ::cfgloader_rs::parse_with::<#value_ty, _, _>(key, #raw, #with)?
+        },
+        (ValueKind::Scalar, None) => {
+            bounds.add_from_str(value_ty);
+            quote_mixed! { ::cfgloader_rs::parse_scalar::<#value_ty>(key, #raw)? }
+        }
+    };
+    // Parse, then run the validation checks against the raw string
+    let mut parse = |raw: proc_macro2::TokenStream| {
+        if checks.is_empty() {
+            return parse(raw);
+        }
+        let value = parse(quote_mixed! { raw.clone() });
+        quote_mixed! {{
+            let raw: String = #raw;
+            let value: #value_ty = #value;
+            #(#checks)*
+            value
+        }}
+    };
+    let wrap = |value: proc_macro2::TokenStream| match optional {
+        Some(_) => quote_mixed! { Some(#value) },
+        None => value,
+    };
+
+    let present = wrap(parse(quote_mixed! { raw }));
+    // Other keys are relative to the same prefix as this field's key
+    let others = |keys: &[syn::LitStr], check: proc_macro2::TokenStream| {
+        (!keys.is_empty()).then(|| {
+            quote_mixed! { ::cfgloader_rs::#check(key, &[#(format!("{}{}", prefix, #keys)),*])?; }
+        })
+    };
+    let requires = others(&attrs.requires, quote_mixed! { check_requires });
+    let conflicts_with = others(&attrs.conflicts_with, quote_mixed! { check_conflicts });
+    let constraints = quote_mixed! { #requires #conflicts_with };
+    let required_if = attrs.required_if.iter().map(|(condition, value)| {
+        quote_mixed! {
+            ::cfgloader_rs::check_required_if(&keys[0], &format!("{}{}", prefix, #condition), #value)?;
+        }
+    });
+    let required_if = quote_mixed! { #(#required_if)* };
+    let missing = if attrs.required.is_some() {
+        quote_mixed! { return Err(::cfgloader_rs::CfgError::MissingEnv(keys.to_vec())) }
+    } else if let Some(def) = &attrs.default {
+        match def {
+            DefaultValue::Str(def) => {
+                // Defaults referencing other variables can only be checked at runtime
+                let interpolated = def.value().contains("${");
+                if attrs.with.is_none() && !interpolated {
+                    builtin::check_default(value_ty, def, &attrs)?;
+                }
+                let value = if interpolated {
+                    wrap(parse(
+                        quote_mixed! { ::cfgloader_rs::interpolate(key, #def)? },
+                    ))
+                } else {
+                    wrap(parse(quote_mixed! { #def.to_string() }))
+                };
+                quote_mixed! {{
+                    let key = keys[0].as_str();
+                    #value
+                }}
+            }
+            DefaultValue::Expr(expr) => wrap(quote_mixed! {{
+                let value: #value_ty = #expr;
+                value
+            }}),
+            DefaultValue::Trait(name) => {
+                bounds.add(value_ty, quote_mixed! { ::std::default::Default });
+                wrap(quote_spanned! {name.span()=>
+                    <#value_ty as ::std::default::Default>::default()
+                })
+            }
+        }
+    } else {
+        // Empty Vec, None for Option<T>, or the type's Default
+        bounds.add(&ty, quote_mixed! { ::std::default::Default });
+        quote_mixed! { Default::default() }
+    };
+
+    let value = quote_mixed! {
+        {
+            // The key first, then its aliases in order
+            let keys = [#(format!("{}{}", prefix, #keys)),*];
+            match ::cfgloader_rs::get_env_first(&keys) {
+                Some((key, raw)) => {
+                    #report_deprecated
+                    #constraints
+                    #present
+                }
+                None => {
+                    #required_if
+                    #missing
+                }
+            }
+        }
+    };
+    Ok(FieldCode {
+        collect: quote_mixed! { ::cfgloader_rs::load_field(#path, || Ok(#value)) },
+        value,
+        keys: quote_mixed! { vec![#(#keys.to_string()),*] },
+    })
+}
+
+/// Generate the expression parsing `raw` into a `Vec<item>`
+fn parse_vec(
+    item: &syn::Type,
+    raw: &proc_macro2::TokenStream,
+    split: &proc_macro2::TokenStream,
+    with: &Option<proc_macro2::TokenStream>,
+    bounds: &mut Bounds,
+) -> proc_macro2::TokenStream {
+    match with {
+        Some(with) => quote_mixed! {
+            ::cfgloader_rs::parse_vec_with::<#item, _, _>(key, #raw, #split, #with)?
+        },
+        None => {
+            bounds.add_from_str(item);
+            quote_mixed! { ::cfgloader_rs::parse_vec::<#item>(key, #raw, #split)? }
+        }
+    }
+}
+
+/// Error on `option` with `msg` if it was given
+fn reject_option<T: quote::ToTokens>(option: &Option<T>, msg: &str) -> syn::Result<()> {
+    match option {
+        Some(option) => Err(syn::Error::new_spanned(option, msg)),
+        None => Ok(()),
     }
-    (None, false)
 }
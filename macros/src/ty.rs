@@ -0,0 +1,121 @@
+//! Recognising the shape of field types.
+//!
+//! Types are matched syntactically on the last path segment, so `Vec<T>`,
+//! `std::vec::Vec<T>` and `alloc::vec::Vec<T>` are all treated alike.
+
+/// How a field's value is parsed from the raw string
+pub(crate) enum ValueKind<'a> {
+    /// `Vec<T>`, parsed element by element
+    Vec(&'a syn::Type),
+    /// `VecDeque<T>` or `Box<[T]>`, parsed as a `Vec<T>` and converted
+    FromVec(&'a syn::Type),
+    /// `HashSet<T>` or `BTreeSet<T>`
+    Set(&'a syn::Type),
+    /// `[T; N]`
+    Array(&'a syn::Type, &'a syn::Expr),
+    /// `HashMap<K, V>` or `BTreeMap<K, V>`
+    Map(&'a syn::Type, &'a syn::Type),
+    /// Anything else, parsed as a whole
+    Scalar,
+}
+
+impl<'a> ValueKind<'a> {
+    pub(crate) fn of(ty: &'a syn::Type) -> Self {
+        if let syn::Type::Array(array) = ty {
+            return ValueKind::Array(&array.elem, &array.len);
+        }
+        if let Some((k, v)) = map_args(ty) {
+            return ValueKind::Map(k, v);
+        }
+        if let Some(item) = generic_arg(ty, "Vec") {
+            return ValueKind::Vec(item);
+        }
+        if let Some(item) = generic_arg(ty, "VecDeque") {
+            return ValueKind::FromVec(item);
+        }
+        if let Some(syn::Type::Slice(slice)) = generic_arg(ty, "Box") {
+            return ValueKind::FromVec(&slice.elem);
+        }
+        if let Some(item) = generic_arg(ty, "HashSet").or_else(|| generic_arg(ty, "BTreeSet")) {
+            return ValueKind::Set(item);
+        }
+        ValueKind::Scalar
+    }
+
+    /// The element type of sequence and set kinds
+    pub(crate) fn item(&self) -> Option<&'a syn::Type> {
+        match *self {
+            ValueKind::Vec(item)
+            | ValueKind::FromVec(item)
+            | ValueKind::Set(item)
+            | ValueKind::Array(item, _) => Some(item),
+            ValueKind::Map(..) | ValueKind::Scalar => None,
+        }
+    }
+}
+
+/// Return `Some((K, V))` if `ty` is `HashMap<K, V, ..>` or `BTreeMap<K, V>`
+fn map_args(ty: &syn::Type) -> Option<(&syn::Type, &syn::Type)> {
+    if let syn::Type::Path(tp) = ty
+        && tp.qself.is_none()
+        && let Some(seg) = tp.path.segments.last()
+        && (seg.ident == "HashMap" || seg.ident == "BTreeMap")
+        && let syn::PathArguments::AngleBracketed(ab) = &seg.arguments
+        && let [
+            syn::GenericArgument::Type(k),
+            syn::GenericArgument::Type(v),
+            ..,
+        ] = &ab.args.iter().collect::<Vec<_>>()[..]
+    {
+        return Some((k, v));
+    }
+    None
+}
+
+/// Return `Some(T)` if `ty` is `Name<T>` (matched on the last path segment), otherwise None
+pub(crate) fn generic_arg<'a>(ty: &'a syn::Type, name: &str) -> Option<&'a syn::Type> {
+    if let syn::Type::Path(tp) = ty
+        && tp.qself.is_none()
+        && let Some(seg) = tp.path.segments.last()
+        && seg.ident == name
+        && let syn::PathArguments::AngleBracketed(ab) = &seg.arguments
+        && let Some(syn::GenericArgument::Type(inner)) = ab.args.first()
+    {
+        return Some(inner);
+    }
+    None
+}
+
+/// Whether `ty` is `std::time::Duration`, written `Duration` or with its full path
+pub(crate) fn is_duration(ty: &syn::Type) -> bool {
+    let syn::Type::Path(tp) = ty else {
+        return false;
+    };
+    if tp.qself.is_some() || tp.path.segments.iter().any(|s| !s.arguments.is_none()) {
+        return false;
+    }
+    let segments: Vec<String> = tp
+        .path
+        .segments
+        .iter()
+        .map(|s| s.ident.to_string())
+        .collect();
+    matches!(
+        segments.iter().map(String::as_str).collect::<Vec<_>>()[..],
+        ["Duration"] | ["std" | "core", "time", "Duration"]
+    )
+}
+
+/// Whether `ty` is written as `bool`
+pub(crate) fn is_bool(ty: &syn::Type) -> bool {
+    matches!(ty, syn::Type::Path(tp) if tp.qself.is_none() && tp.path.is_ident("bool"))
+}
+
+/// Whether `ty` is written as a built-in integer type such as `u16`
+pub(crate) fn is_integer(ty: &syn::Type) -> bool {
+    const INTEGERS: &[&str] = &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+    ];
+    matches!(ty, syn::Type::Path(tp) if tp.qself.is_none()
+        && tp.path.get_ident().is_some_and(|ident| INTEGERS.iter().any(|int| ident == int)))
+}
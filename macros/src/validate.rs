@@ -0,0 +1,176 @@
+//! Code generation for the validation options `range`, `len`, `regex` and `one_of`.
+//!
+//! The checks run on the parsed value and report the raw value together with
+//! the rule as written in the attribute. `len` applies to the whole value; the
+//! other rules apply to a scalar, or to each element of a collection.
+
+use proc_macro2::TokenStream;
+use quote::ToTokens;
+use syn::LitStr;
+
+use crate::attr::{FieldAttrs, LenRule};
+use crate::ty::ValueKind;
+
+/// Generate the checks run on `value`, with `key` and `raw` in scope
+pub(crate) fn checks(attrs: &FieldAttrs, kind: &ValueKind) -> syn::Result<Vec<TokenStream>> {
+    let mut checks = Vec::new();
+    if let Some(len) = &attrs.len {
+        let bound = |bound: &Option<syn::Expr>| match bound {
+            Some(bound) => quote_mixed! { Some(#bound) },
+            None => quote_mixed! { None },
+        };
+        let (min, max) = (bound(&len.min), bound(&len.max));
+        let rule = len_rule(len);
+        checks.push(quote_mixed! {
+            ::cfgloader_rs::check_len(key, &raw, &value, #min, #max, #rule)?;
+        });
+    }
+
+    let mut item_checks = Vec::new();
+    if let Some(range) = &attrs.range {
+        let rule = range_rule(range);
+        item_checks.push(quote_mixed! {
+            ::cfgloader_rs::check_range(key, &raw, item, #range, #rule)?;
+        });
+    }
+    if let Some(options) = &attrs.one_of {
+        let rule = one_of_rule(options);
+        let options = &options.elems;
+        item_checks.push(quote_mixed! {
+            ::cfgloader_rs::check_one_of(key, &raw, item, &[#options], #rule)?;
+        });
+    }
+    if let Some(pattern) = &attrs.regex {
+        check_pattern(pattern)?;
+        let rule = format!("regex = {:?}", pattern.value());
+        item_checks.push(quote_mixed! {
+            ::cfgloader_rs::check_regex(
+                key,
+                &raw,
+                ::std::convert::AsRef::<str>::as_ref(item),
+                #pattern,
+                #rule,
+            )?;
+        });
+    }
+    if item_checks.is_empty() {
+        return Ok(checks);
+    }
+
+    match kind {
+        ValueKind::Scalar => checks.push(quote_mixed! {{
+            let item = &value;
+            #(#item_checks)*
+        }}),
+        ValueKind::Map(..) => {
+            return Err(syn::Error::new_spanned(
+                &attrs.attr,
+                "`range`, `regex` and `one_of` are not supported on map fields",
+            ));
+        }
+        _ => checks.push(quote_mixed! {
+            for item in value.iter() {
+                #(#item_checks)*
+            }
+        }),
+    }
+    Ok(checks)
+}
+
+/// The rule reported for `len`, e.g. `len(min = 1, max = 64)`
+fn len_rule(len: &LenRule) -> String {
+    let bounds: Vec<String> = [("min", &len.min), ("max", &len.max)]
+        .into_iter()
+        .filter_map(|(name, bound)| Some(format!("{name} = {}", compact(bound.as_ref()?))))
+        .collect();
+    format!("len({})", bounds.join(", "))
+}
+
+/// The rule reported for `range`, e.g. `range(1024..=65535)`
+fn range_rule(range: &syn::ExprRange) -> String {
+    format!("range({})", compact(range))
+}
+
+/// The rule reported for `one_of`, e.g. `one_of = ["debug", "info"]`
+fn one_of_rule(options: &syn::ExprArray) -> String {
+    let options: Vec<String> = options
+        .elems
+        .iter()
+        .map(|option| match option {
+            // Keep the spaces inside string literals
+            syn::Expr::Lit(lit) => lit.to_token_stream().to_string(),
+            option => compact(option),
+        })
+        .collect();
+    format!("one_of = [{}]", options.join(", "))
+}
+
+/// The tokens of `expr` without whitespace, e.g. `1024..=65535`
+fn compact(expr: &impl ToTokens) -> String {
+    expr.to_token_stream()
+        .to_string()
+        .split_whitespace()
+        .collect()
+}
+
+/// Reject invalid patterns during expansion rather than on every load
+#[cfg(feature = "regex")]
+fn check_pattern(pattern: &LitStr) -> syn::Result<()> {
+    match regex::Regex::new(&pattern.value()) {
+        Ok(_) => Ok(()),
+        Err(e) => Err(syn::Error::new(
+            pattern.span(),
+            format!("invalid regex: {e}"),
+        )),
+    }
+}
+
+#[cfg(not(feature = "regex"))]
+fn check_pattern(pattern: &LitStr) -> syn::Result<()> {
+    Err(syn::Error::new(
+        pattern.span(),
+        "`regex` requires the `regex` feature of cfgloader_rs",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn len_rule_lists_the_given_bounds() {
+        let len = |min, max| LenRule {
+            name: parse_quote!(len),
+            min,
+            max,
+        };
+        let rule = len_rule(&len(Some(parse_quote!(1)), Some(parse_quote!(64))));
+        assert_eq!(rule, "len(min = 1, max = 64)");
+        assert_eq!(len_rule(&len(Some(parse_quote!(1)), None)), "len(min = 1)");
+        let rule = len_rule(&len(None, Some(parse_quote!(MAX_LEN * 2))));
+        assert_eq!(rule, "len(max = MAX_LEN*2)");
+    }
+
+    #[test]
+    fn range_rule_drops_whitespace() {
+        assert_eq!(
+            range_rule(&parse_quote!(1024..=65535)),
+            "range(1024..=65535)"
+        );
+        assert_eq!(range_rule(&parse_quote!(0.0..1.0)), "range(0.0..1.0)");
+        assert_eq!(range_rule(&parse_quote!(..MAX)), "range(..MAX)");
+        assert_eq!(
+            range_rule(&parse_quote!(limits::MIN..=limits::max(2))),
+            "range(limits::MIN..=limits::max(2))"
+        );
+    }
+
+    #[test]
+    fn one_of_rule_keeps_string_literals() {
+        let rule = one_of_rule(&parse_quote!(["debug", "info", "two words"]));
+        assert_eq!(rule, r#"one_of = ["debug", "info", "two words"]"#);
+        let rule = one_of_rule(&parse_quote!([1, -1, Level::Warn, 2 * 4]));
+        assert_eq!(rule, "one_of = [1, -1, Level::Warn, 2*4]");
+    }
+}